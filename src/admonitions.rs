@@ -0,0 +1,239 @@
+use core::hash::BuildHasher;
+use std::collections::HashMap;
+
+use crate::fences::fenced_lines;
+use crate::{escape, ConfigAdmonition};
+
+// The GitHub alert types. They can be changed and more types can be added in the config.
+pub fn default_admonitions() -> HashMap<String, ConfigAdmonition> {
+    [
+        ("note", "Note", "is-info"),
+        ("tip", "Tip", "is-success"),
+        ("important", "Important", "is-link"),
+        ("warning", "Warning", "is-warning"),
+        ("caution", "Caution", "is-danger"),
+    ]
+    .into_iter()
+    .map(|(name, title, class)| {
+        (
+            name.to_owned(),
+            ConfigAdmonition {
+                title: title.to_owned(),
+                class: class.to_owned(),
+            },
+        )
+    })
+    .collect()
+}
+
+// Convert the GitHub style alerts
+//
+//     > [!NOTE] Optional title
+//     > Text
+//
+// and the fenced containers
+//
+//     :::note Optional title
+//     Text
+//     :::
+//
+// to Bulma messages. The content stays markdown, separated by empty lines from the HTML around it.
+pub fn convert_admonitions<S: BuildHasher>(
+    content: &str,
+    types: &HashMap<String, ConfigAdmonition, S>,
+    math: bool,
+) -> Result<String, String> {
+    let lines = content.split('\n').collect::<Vec<_>>();
+    let fenced = fenced_lines(content, math);
+    let mut output: Vec<String> = vec![];
+    let mut index = 0;
+
+    while index < lines.len() {
+        let line = lines[index];
+        index += 1;
+
+        if fenced[index - 1] {
+            output.push(line.to_owned());
+            continue;
+        }
+
+        // The marker has to be on the first line of the quote
+        let in_quote = index >= 2 && lines[index - 2].starts_with('>');
+        if let Some((name, title)) = alert_start(line).filter(|_start| !in_quote) {
+            let mut body = vec![];
+            while index < lines.len() && lines[index].starts_with('>') {
+                let row = lines[index].trim_start_matches('>');
+                body.push(row.strip_prefix(' ').unwrap_or(row));
+                index += 1;
+            }
+            output.push(render(name, title, &body.join("\n"), types, math)?);
+            continue;
+        }
+
+        if let Some((name, title)) = container_start(line) {
+            let mut body = vec![];
+            let mut depth = 0;
+            let mut closed = false;
+            while index < lines.len() {
+                let row = lines[index];
+                index += 1;
+                if row.trim_end() == ":::" {
+                    if depth == 0 {
+                        closed = true;
+                        break;
+                    }
+                    depth -= 1;
+                }
+                if container_start(row).is_some() {
+                    depth += 1;
+                }
+                body.push(row);
+            }
+            if !closed {
+                return Err(format!("The ':::{name}' block is not closed"));
+            }
+            output.push(render(name, title, &body.join("\n"), types, math)?);
+            continue;
+        }
+
+        output.push(line.to_owned());
+    }
+
+    Ok(output.join("\n"))
+}
+
+// `> [!NOTE]` or `> [!NOTE] Title`
+fn alert_start(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix('>')?.trim_start().strip_prefix("[!")?;
+    let (name, title) = rest.split_once(']')?;
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|chr| chr.is_ascii_alphanumeric() || chr == '-')
+    {
+        return None;
+    }
+    Some((name, title.trim()))
+}
+
+// `:::note` or `:::note Title`
+fn container_start(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix(":::")?.trim_start();
+    let (name, title) = rest.split_once(' ').unwrap_or((rest, ""));
+    if name.is_empty() {
+        return None;
+    }
+    Some((name, title.trim()))
+}
+
+fn render<S: BuildHasher>(
+    name: &str,
+    title: &str,
+    body: &str,
+    types: &HashMap<String, ConfigAdmonition, S>,
+    math: bool,
+) -> Result<String, String> {
+    let name = name.to_lowercase();
+    let admonition = types.get(&name).ok_or_else(|| {
+        let mut names = types.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        format!(
+            "Unknown admonition type '{name}'. Available types: {}",
+            names.join(", ")
+        )
+    })?;
+    let title = if title.is_empty() {
+        &admonition.title
+    } else {
+        title
+    };
+    let body = convert_admonitions(body, types, math)?;
+
+    Ok(format!(
+        r#"<article class="message {} admonition admonition-{name}">
+<div class="message-header"><p>{}</p></div>
+<div class="message-body">
+
+{body}
+
+</div>
+</article>
+"#,
+        admonition.class,
+        escape(title)
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn github_alert() {
+        let html = convert_admonitions(
+            "Before\n\n> [!WARNING]\n> Be *careful*\n>\n> Really\n\nAfter",
+            &default_admonitions(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            html,
+            r#"Before
+
+<article class="message is-warning admonition admonition-warning">
+<div class="message-header"><p>Warning</p></div>
+<div class="message-body">
+
+Be *careful*
+
+Really
+
+</div>
+</article>
+
+
+After"#
+        );
+    }
+
+    #[test]
+    fn container_with_title() {
+        let html = convert_admonitions(
+            ":::tip Use <cargo>\nOutside\n:::note\nInside\n:::\n:::\n```\n:::note\n```",
+            &default_admonitions(),
+            false,
+        )
+        .unwrap();
+        assert!(html.starts_with(
+            "<article class=\"message is-success admonition admonition-tip\">\n<div class=\"message-header\"><p>Use &lt;cargo&gt;</p></div>"
+        ));
+        assert!(html
+            .contains("Outside\n<article class=\"message is-info admonition admonition-note\">"));
+        assert!(html.ends_with("```\n:::note\n```"));
+    }
+
+    #[test]
+    fn plain_quote() {
+        let text = "> Just a quote\n> [!NOTE] not at the start";
+        assert_eq!(
+            convert_admonitions(text, &default_admonitions(), false).unwrap(),
+            text
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            convert_admonitions("> [!DANGER]\n> text", &default_admonitions(), false)
+                .err()
+                .unwrap(),
+            "Unknown admonition type 'danger'. Available types: caution, important, note, tip, warning"
+        );
+        assert_eq!(
+            convert_admonitions(":::note\ntext", &default_admonitions(), false)
+                .err()
+                .unwrap(),
+            "The ':::note' block is not closed"
+        );
+    }
+}
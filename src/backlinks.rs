@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use crate::redirects::collect_redirects;
+use crate::{fences, is_external, Config, Link, Page};
+
+// Following more redirects than this means they form a loop
+const MAX_REDIRECTS: usize = 10;
+
+// Add to each page the pages linking to it. The links are taken from the markdown of the pages so
+// the links in code are left out and reference links are included. Links to the URL of the site,
+// relative links and links to redirect pages all count as links to the page they end up at.
+pub fn collect_backlinks(config: &Config, pages: Vec<Page>) -> Vec<Page> {
+    log::info!("collect_backlinks");
+
+    let redirects = collect_redirects(&pages)
+        .into_iter()
+        .map(|redirect| {
+            (
+                redirect.from.trim_start_matches('/').to_owned(),
+                redirect.to,
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut links: HashMap<String, Vec<Link>> = HashMap::new();
+    for page in &pages {
+        for (text, url) in find_links(config, page) {
+            let Some(path) = resolve(config, &page.url_path, &url) else {
+                continue;
+            };
+            let path = follow_redirects(config, &redirects, path);
+            if path == page.url_path {
+                continue;
+            }
+            let backlinks = links.entry(path.clone()).or_default();
+            if backlinks.iter().any(|link| link.from_path == page.url_path) {
+                continue;
+            }
+            backlinks.push(Link {
+                from_title: page.title.clone(),
+                from_path: page.url_path.clone(),
+                to_title: text,
+                to_path: format!("/{path}"),
+            });
+        }
+    }
+
+    pages
+        .into_iter()
+        .map(|mut page| {
+            let mut backlinks = links.remove(&page.url_path).unwrap_or_default();
+            backlinks.sort_by(|a, b| b.from_title.cmp(&a.from_title));
+            page.backlinks = backlinks;
+            page
+        })
+        .collect()
+}
+
+// The text and the URL of the inline and reference links of the page
+fn find_links(config: &Config, page: &Page) -> Vec<(String, String)> {
+    fences::targets(&page.content, config.math)
+        .into_iter()
+        .filter_map(|target| Some((target.text?, target.url)))
+        .collect()
+}
+
+// The url_path of the page a link points to, None for links outside the site.
+// `from` is the url_path of the page with the link, relative links are resolved from its directory.
+fn resolve(config: &Config, from: &str, url: &str) -> Option<String> {
+    let path = url.split(['#', '?']).next().unwrap_or_default();
+    if path.is_empty() {
+        // A link to an anchor of the same page
+        return None;
+    }
+
+    let absolute = if is_external(path) {
+        let site = without_scheme(&config.url);
+        let rest = without_scheme(path).strip_prefix(site)?;
+        if !rest.is_empty() && !rest.starts_with('/') {
+            // e.g. https://rust.code-maven.com.evil.com/
+            return None;
+        }
+        rest.to_owned()
+    } else if path.starts_with('/') {
+        path.to_owned()
+    } else {
+        match from.rsplit_once('/') {
+            Some((dir, _page)) => format!("/{dir}/{path}"),
+            None => format!("/{path}"),
+        }
+    };
+
+    // Remove the . and .. parts of the path
+    let mut parts: Vec<&str> = vec![];
+    for part in absolute.split('/').skip(1) {
+        match part {
+            "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    let joined = parts.join("/");
+
+    let page = joined.strip_suffix(".html").unwrap_or(&joined);
+    // The index.html of a directory
+    let page = page
+        .strip_suffix("index")
+        .filter(|dir| dir.is_empty() || dir.ends_with('/'))
+        .unwrap_or(page);
+    Some(page.to_owned())
+}
+
+fn without_scheme(url: &str) -> &str {
+    url.split_once("//").map_or(url, |(_scheme, rest)| rest)
+}
+
+fn follow_redirects<S: core::hash::BuildHasher>(
+    config: &Config,
+    redirects: &HashMap<String, String, S>,
+    mut path: String,
+) -> String {
+    for _ in 0..MAX_REDIRECTS {
+        let Some(target) = redirects
+            .get(&path)
+            .and_then(|target| resolve(config, "", target))
+        else {
+            break;
+        };
+        path = target;
+    }
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::read_config;
+
+    #[test]
+    fn paths() {
+        let config = read_config("test_cases/demo").unwrap();
+        let cases = [
+            ("/sub", Some("sub")),
+            ("/sub.html#part", Some("sub")),
+            ("/?page=2", Some("")),
+            ("/index.html", Some("")),
+            ("/blog/index.html", Some("blog/")),
+            ("https://rust.code-maven.com/sub", Some("sub")),
+            ("http://rust.code-maven.com/", Some("")),
+            ("https://rust.code-maven.com.example.com/", None),
+            ("https://example.com/sub", None),
+            ("mailto:foo@bar.com", None),
+            ("#part", None),
+            ("other", Some("blog/other")),
+            ("./other", Some("blog/other")),
+            ("../sub", Some("sub")),
+        ];
+        assert_eq!(resolve(&config, "", "sub").as_deref(), Some("sub"));
+        for (url, expected) in cases {
+            assert_eq!(
+                resolve(&config, "blog/post", url).as_deref(),
+                expected,
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn backlinks() {
+        let config = read_config("test_cases/demo").unwrap();
+        let pages = vec![
+            Page {
+                title: String::from("First"),
+                content: String::from(
+                    "See [the target](/target#part), [again](target.html) and [by URL][site].\n\n[site]: https://rust.code-maven.com/target\n",
+                ),
+                ..Page::for_test("first", &[])
+            },
+            Page {
+                title: String::from("Second"),
+                content: String::from(
+                    "`[in code](/target)`\n\n```\n[in a block](/target)\n```\n\n[Old](/old)\n",
+                ),
+                ..Page::for_test("second", &[])
+            },
+            Page {
+                title: String::from("Third"),
+                content: String::from("[Self](/third) [Out](https://example.com/)"),
+                ..Page::for_test("third", &[])
+            },
+            Page {
+                title: String::from("Old"),
+                redirect: Some(String::from("/target")),
+                ..Page::for_test("old", &[])
+            },
+            Page {
+                title: String::from("Target"),
+                ..Page::for_test("target", &[])
+            },
+        ];
+
+        let pages = collect_backlinks(&config, pages);
+        let target = pages.iter().find(|page| page.url_path == "target").unwrap();
+        assert_eq!(
+            target.backlinks,
+            vec![
+                Link {
+                    from_title: String::from("Second"),
+                    from_path: String::from("second"),
+                    to_title: String::from("Old"),
+                    to_path: String::from("/target"),
+                },
+                Link {
+                    from_title: String::from("First"),
+                    from_path: String::from("first"),
+                    to_title: String::from("the target"),
+                    to_path: String::from("/target"),
+                },
+            ]
+        );
+        assert!(pages
+            .iter()
+            .all(|page| page.url_path == "target" || page.backlinks.is_empty()));
+    }
+}
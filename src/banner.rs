@@ -0,0 +1,301 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use banner_builder::{Banner, Embed, Text};
+use image::imageops::FilterType;
+
+use crate::{stable_hash, Config, ConfigBanner, Page};
+
+// Draw the Open Graph image of a page, or of the archive if there is no page, to `image_file`.
+// The banners are kept in the cache by the hash of everything that goes into them so unchanged
+// banners are not drawn again. Returns false if there is no image, e.g. because the title is too long.
+pub fn draw_banner(
+    config: &Config,
+    root: &str,
+    title: &str,
+    page: Option<&Page>,
+    image_file: &Path,
+) -> Result<bool, String> {
+    let (mut banner, background_image) = build_banner(config, root, title, page)?;
+    for embed in &banner.embed {
+        check_fits(&banner, embed)?;
+    }
+
+    let mut parts = vec![serde_json::to_vec(&banner).map_err(|err| err.to_string())?];
+    for file in banner
+        .embed
+        .iter()
+        .map(|embed| PathBuf::from(&embed.file))
+        .chain(background_image.clone())
+    {
+        parts.push(read(&file)?);
+    }
+    let hash = stable_hash(&parts.iter().map(Vec::as_slice).collect::<Vec<_>>());
+    let cache = config.banner.as_ref().map_or_else(
+        || ConfigBanner::default().cache,
+        |settings| settings.cache.clone(),
+    );
+    let cache_dir = Path::new(root).join(cache).join("banners");
+    let cache_file = cache_dir.join(format!("{hash}.png"));
+
+    if cache_file.exists() {
+        log::info!("Using cached banner of '{title}'");
+    } else {
+        fs::create_dir_all(&cache_dir).map_err(|err| err.to_string())?;
+
+        // The background image covers the whole banner and is drawn first
+        let background_file = cache_file.with_extension("background.png");
+        if let Some(background_image) = &background_image {
+            image::open(background_image)
+                .map_err(|err| format!("Could not open {:?}: {err}", background_image.display()))?
+                .resize_to_fill(banner.width, banner.height, FilterType::Lanczos3)
+                .save(&background_file)
+                .map_err(|err| err.to_string())?;
+            banner.embed.insert(
+                0,
+                Embed {
+                    file: background_file.display().to_string(),
+                    x: 0,
+                    y: 0,
+                    width: None,
+                },
+            );
+        }
+
+        let drawn = banner_builder::draw_image(&banner, Path::new(""), &cache_file);
+        if background_image.is_some() {
+            fs::remove_file(&background_file).map_err(|err| err.to_string())?;
+        }
+        if !drawn {
+            return Ok(false);
+        }
+    }
+
+    fs::copy(&cache_file, image_file).map_err(|err| {
+        format!(
+            "Could not copy the banner to {:?}: {err}",
+            image_file.display()
+        )
+    })?;
+    Ok(true)
+}
+
+// The banner of the config with the changes in the front matter of the page and the background image
+fn build_banner(
+    config: &Config,
+    root: &str,
+    title: &str,
+    page: Option<&Page>,
+) -> Result<(Banner, Option<PathBuf>), String> {
+    let default = ConfigBanner::default();
+    let settings = config.banner.as_ref().unwrap_or(&default);
+    let overrides = page
+        .and_then(|page| page.banner.clone())
+        .unwrap_or_default();
+
+    let background_color = overrides
+        .background_color
+        .unwrap_or_else(|| settings.background_color.clone());
+    check_color(&background_color)?;
+    let background_image = overrides
+        .background_image
+        .or_else(|| settings.background_image.clone())
+        .map(|file| Path::new(root).join(file));
+
+    let author = page
+        .and_then(|page| {
+            config
+                .authors
+                .iter()
+                .find(|author| author.nickname == page.author)
+        })
+        .map(|author| author.name.clone())
+        .unwrap_or_default();
+    let date = page
+        .map(|page| page.date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    let fill = |text: &str| {
+        text.replace("{title}", title)
+            .replace("{site_name}", &config.site_name)
+            .replace("{author}", &author)
+            .replace("{date}", &date)
+    };
+
+    let lines = settings
+        .lines
+        .iter()
+        .map(|line| {
+            check_color(&line.color)?;
+            Ok(Text {
+                text: fill(&line.text),
+                x: line.x,
+                y: line.y,
+                rtl: line.rtl,
+                color: line.color.clone(),
+                size: line.size,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let banner = Banner {
+        width: settings.width,
+        height: settings.height,
+        text: title.to_owned(),
+        size: overrides.size.unwrap_or(settings.size),
+        background_color,
+        embed: settings
+            .embed
+            .iter()
+            .map(|embed| Embed {
+                file: Path::new(root).join(&embed.file).display().to_string(),
+                x: embed.x,
+                y: embed.y,
+                width: embed.width,
+            })
+            .collect(),
+        lines,
+    };
+
+    Ok((banner, background_image))
+}
+
+fn read(file: &Path) -> Result<Vec<u8>, String> {
+    fs::read(file).map_err(|err| {
+        format!(
+            "Could not read the banner image {:?}: {err}",
+            file.display()
+        )
+    })
+}
+
+// RRGGBB or RRGGBBAA
+fn check_color(color: &str) -> Result<(), String> {
+    if (color.len() == 6 || color.len() == 8) && color.chars().all(|chr| chr.is_ascii_hexdigit()) {
+        return Ok(());
+    }
+    Err(format!(
+        "Invalid color '{color}' in the banner. Expected RRGGBB or RRGGBBAA in hex"
+    ))
+}
+
+// banner_builder panics if an embedded image does not fit
+fn check_fits(banner: &Banner, embed: &Embed) -> Result<(), String> {
+    let (width, height) = image::image_dimensions(&embed.file)
+        .map_err(|err| format!("Could not read the banner image {:?}: {err}", embed.file))?;
+    let (width, height) = embed.width.map_or((width, height), |resized| {
+        (
+            resized,
+            resized
+                .saturating_mul(height)
+                .checked_div(width)
+                .unwrap_or(0),
+        )
+    });
+    if embed.x + width > banner.width || embed.y + height > banner.height {
+        return Err(format!(
+            "The image {:?} of {width}x{height} at {},{} does not fit in the banner of {}x{}",
+            embed.file, embed.x, embed.y, banner.width, banner.height
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::PageBanner;
+
+    #[test]
+    fn colors() {
+        assert!(check_color("1E2A38").is_ok());
+        assert!(check_color("1E2A38FF").is_ok());
+        assert_eq!(
+            check_color("blue").err().unwrap(),
+            "Invalid color 'blue' in the banner. Expected RRGGBB or RRGGBBAA in hex"
+        );
+    }
+
+    #[test]
+    fn cached_banner() {
+        let root = tempdir::TempDir::new("banner").unwrap();
+        let root_str = root.path().to_str().unwrap();
+        image::RgbaImage::from_pixel(200, 100, image::Rgba([0, 0, 200, 255]))
+            .save(root.path().join("background.png"))
+            .unwrap();
+
+        let mut config = crate::read_config("test_cases/demo").unwrap();
+        config.banner = Some(ConfigBanner {
+            width: 400,
+            height: 200,
+            lines: vec![Text {
+                text: String::from("{site_name}"),
+                x: 10,
+                y: 170,
+                rtl: false,
+                color: String::from("FFFFFF"),
+                size: 16,
+            }],
+            ..ConfigBanner::default()
+        });
+        let page = Page {
+            banner: Some(PageBanner {
+                background_image: Some(String::from("background.png")),
+                ..PageBanner::default()
+            }),
+            ..Page::default()
+        };
+
+        let image_file = root.path().join("title.png");
+        assert!(draw_banner(&config, root_str, "Title", Some(&page), &image_file).unwrap());
+        let banner = image::open(&image_file).unwrap().to_rgba8();
+        assert_eq!((banner.width(), banner.height()), (400, 200));
+        assert_eq!(banner.get_pixel(1, 1), &image::Rgba([0, 0, 200, 255]));
+
+        // The second time the banner comes from the cache
+        let cached = fs::read_dir(root.path().join(".cache/banners"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(cached.len(), 1);
+        fs::write(&cached[0], "cached").unwrap();
+        assert!(draw_banner(&config, root_str, "Title", Some(&page), &image_file).unwrap());
+        assert_eq!(fs::read_to_string(&image_file).unwrap(), "cached");
+
+        // A different title is a different banner
+        assert!(draw_banner(&config, root_str, "Other", Some(&page), &image_file).unwrap());
+        assert_eq!(
+            fs::read_dir(root.path().join(".cache/banners"))
+                .unwrap()
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn embed_does_not_fit() {
+        let mut config = crate::read_config("test_cases/demo").unwrap();
+        config.banner = Some(ConfigBanner {
+            embed: vec![Embed {
+                file: String::from("examples/files/code_maven_440x440.png"),
+                x: 700,
+                y: 10,
+                width: None,
+            }],
+            ..ConfigBanner::default()
+        });
+        let error = draw_banner(
+            &config,
+            "test_cases/demo",
+            "Title",
+            None,
+            Path::new("x.png"),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            error,
+            "The image \"test_cases/demo/examples/files/code_maven_440x440.png\" of 440x440 at 700,10 does not fit in the banner of 1000x500"
+        );
+    }
+}
@@ -1,13 +1,55 @@
-use crate::{include_tag, latest_tag, youtube_tag, Config, Page};
+use std::sync::LazyLock;
 
-pub fn process_curly_tags(config: &Config, root: &str, pages: Vec<Page>) -> Vec<Page> {
+use liquid_core::ParseTag;
+use regex::{Captures, Regex};
+
+use crate::details_tag::DetailsBlock;
+use crate::embed_tag::EmbedTag;
+use crate::fences::{fenced_lines, inline_math};
+use crate::{include_tag, latest_tag, output_tag, shortcode_tag, youtube_tag, Config, Page};
+
+// The code is replaced by \u{E000}index\u{E001} while the Liquid tags are processed
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("\u{E000}([0-9]+)\u{E001}").unwrap());
+
+// The location in the errors of the Liquid parser, e.g. " --> 3:4"
+static POSITION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r" --> ([0-9]+):").unwrap());
+
+pub fn process_curly_tags(
+    config: &Config,
+    root: &str,
+    outdir: &str,
+    pages: Vec<Page>,
+) -> Vec<Page> {
     log::info!("process_curly_tags");
 
     let all_pages = pages.clone();
-    let parser = liquid::ParserBuilder::with_stdlib()
-        .tag(latest_tag::LatestTag)
-        .tag(youtube_tag::YoutubeTag)
-        .tag(include_tag::IncludeTag)
+    let tags: Vec<Box<dyn ParseTag>> = EmbedTag::ALL
+        .into_iter()
+        .map(Into::into)
+        .chain([
+            latest_tag::LatestTag.into(),
+            youtube_tag::YoutubeTag.into(),
+            include_tag::IncludeTag.into(),
+            output_tag::OutputTag.into(),
+        ])
+        .collect();
+    let builtin = tags
+        .iter()
+        .map(|tag| tag.reflection().tag())
+        .chain(["details"])
+        .collect::<Vec<_>>();
+    let shortcodes = match shortcode_tag::read_shortcodes(root, &builtin) {
+        Ok(shortcodes) => shortcodes,
+        Err(err) => panic!("{err}"),
+    };
+    let parser = tags
+        .into_iter()
+        .chain(shortcodes.into_iter().map(Into::into))
+        .fold(liquid::ParserBuilder::with_stdlib(), |builder, tag| {
+            builder.tag(tag)
+        })
+        .block(DetailsBlock)
         .build()
         .unwrap();
 
@@ -15,62 +57,279 @@ pub fn process_curly_tags(config: &Config, root: &str, pages: Vec<Page>) -> Vec<
         .into_iter()
         .map(|mut page| {
             log::info!("process_curly_tags: {}", page.filename);
-            let mut in_code = false;
-            page.content = page
-                .content
-                .split('\n')
-                .map(|row| {
-                    if row.starts_with("```") {
-                        in_code = !in_code;
-                    }
-                    if in_code {
-                        return row.to_owned();
-                    }
-                    if !row.contains('{') {
-                        return row.to_owned();
-                    }
-                    match process_curly_tags_for_text(config, root, row, &all_pages, &parser) {
-                        Ok(val) => val,
-                        Err(err) => panic!("Error while parsing '{}': {err}", page.filename),
-                    }
-                })
-                .collect::<Vec<String>>()
-                .join("\n");
+            if !page.content.contains('{') {
+                return page;
+            }
+
+            // The whole page is one template so the tags can span lines and have a body
+            let (text, code) = protect_code(&page.content, config.math);
+            if let Some(line) = find_unclosed_tag(&text) {
+                panic!(
+                    "Unclosed tag '{}' in '{}' line {}",
+                    page.content.split('\n').nth(line - 1).unwrap_or_default(),
+                    page.filename,
+                    page.content_line + line - 1
+                );
+            }
+            let render = |source: &str, dry_run: bool| {
+                let globals =
+                    page_globals(config, root, outdir, &page.url_path, &all_pages, dry_run);
+                process_curly_tags_for_text(source, &parser, &globals)
+            };
+            match render(&text, false) {
+                Ok(val) => page.content = restore_code(&val, &code),
+                Err(err) => match find_error_line(&text, &err, |row| render(row, true)) {
+                    Some(line) => panic!(
+                        "Error while parsing '{}' line {}: {err}",
+                        page.filename,
+                        page.content_line + line - 1
+                    ),
+                    None => panic!("Error while parsing '{}': {err}", page.filename),
+                },
+            }
             page
         })
         .collect::<Vec<Page>>();
     pages
 }
 
-fn process_curly_tags_for_text(
+// Replace the lines of the code blocks and the inline code (and, if math is enabled, the $$ blocks and
+// the $...$ spans) by placeholders so Liquid leaves them alone.
+// The newlines stay in place so the line numbers in the template are the same as in the page.
+fn protect_code(content: &str, math: bool) -> (String, Vec<String>) {
+    let mut code = vec![];
+    let fenced = fenced_lines(content, math);
+    let content = if math {
+        protect_inline_math(content, &mut code)
+    } else {
+        content.to_owned()
+    };
+
+    let text = content
+        .split('\n')
+        .zip(fenced)
+        .map(|(row, fenced)| {
+            if fenced {
+                code.push(row.to_owned());
+                return placeholder(code.len() - 1);
+            }
+            protect_inline_code(row, &mut code)
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    (text, code)
+}
+
+// $x^{2}$ Each line of a span is replaced separately to keep the newlines.
+fn protect_inline_math(content: &str, code: &mut Vec<String>) -> String {
+    let mut output = String::new();
+    let mut end = 0;
+    for span in inline_math(content) {
+        output += content.get(end..span.start).unwrap_or_default();
+        let lines = content
+            .get(span.clone())
+            .unwrap_or_default()
+            .split('\n')
+            .map(|line| {
+                code.push(line.to_owned());
+                placeholder(code.len() - 1)
+            })
+            .collect::<Vec<_>>();
+        output += &lines.join("\n");
+        end = span.end;
+    }
+    output += content.get(end..).unwrap_or_default();
+
+    output
+}
+
+// `code` or ``code with a ` in it``
+pub fn protect_inline_code(row: &str, code: &mut Vec<String>) -> String {
+    let mut output = String::new();
+    let mut rest = row;
+
+    while let Some(start) = rest.find('`') {
+        let (before, from_tick) = rest.split_at(start);
+        let ticks = from_tick.len() - from_tick.trim_start_matches('`').len();
+        let (fence, after_fence) = from_tick.split_at(ticks);
+
+        output += before;
+        if let Some(index) = closing_ticks(after_fence, ticks) {
+            let (span, after) = from_tick.split_at(ticks + index + ticks);
+            code.push(span.to_owned());
+            output += &placeholder(code.len() - 1);
+            rest = after;
+        } else {
+            output += fence;
+            rest = after_fence;
+        }
+    }
+    output += rest;
+
+    output
+}
+
+// The closing run of backticks has to be of the same length as the opening one
+fn closing_ticks(text: &str, ticks: usize) -> Option<usize> {
+    let mut run = 0;
+    for (index, chr) in text.char_indices().chain([(text.len(), ' ')]) {
+        if chr == '`' {
+            run += 1;
+            continue;
+        }
+        if run == ticks {
+            return Some(index - ticks);
+        }
+        run = 0;
+    }
+    None
+}
+
+fn placeholder(index: usize) -> String {
+    format!("\u{E000}{index}\u{E001}")
+}
+
+pub fn restore_code(text: &str, code: &[String]) -> String {
+    PLACEHOLDER
+        .replace_all(text, |captures: &Captures| {
+            captures[1]
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| code.get(index))
+                .cloned()
+                .unwrap_or_default()
+        })
+        .into_owned()
+}
+
+// The line of a `{%` or `{{` that is not closed. The parser would report the end of the page.
+fn find_unclosed_tag(text: &str) -> Option<usize> {
+    [("{%", "%}"), ("{{", "}}")]
+        .into_iter()
+        .filter_map(|(open, close)| {
+            text.match_indices(open)
+                .map(|(index, _open)| text.split_at(index))
+                .find(|(_before, after)| !after.contains(close))
+                .map(|(before, _after)| before.matches('\n').count() + 1)
+        })
+        .min()
+}
+
+// The line number in the template where the error happened. The parser reports it for the syntax errors,
+// for the others we look for the line that fails with the same error on its own.
+// The lines are rendered as a dry run so the examples are not run again and no files are written.
+fn find_error_line(
+    text: &str,
+    err: &liquid_core::Error,
+    render: impl Fn(&str) -> Result<String, liquid_core::Error>,
+) -> Option<usize> {
+    let message = err.to_string();
+    if let Some(captures) = POSITION.captures(&message) {
+        return captures[1].parse().ok();
+    }
+
+    text.split('\n')
+        .enumerate()
+        .filter(|(_index, row)| row.contains('{'))
+        .find(|(_index, row)| {
+            // The error of the whole page also lists the blocks around the tag
+            render(row).is_err_and(|row_err| {
+                row_err
+                    .to_string()
+                    .lines()
+                    .all(|line| message.lines().any(|message_line| message_line == line))
+            })
+        })
+        .map(|(index, _row)| index + 1)
+}
+
+fn page_globals(
     config: &Config,
     root: &str,
-    text: &str,
+    outdir: &str,
+    url_path: &str,
     all_pages: &[Page],
+    dry_run: bool,
+) -> liquid::Object {
+    liquid::object!({"items": all_pages, "url_path": url_path, "branch": config.branch, "repo": config.repo , "root": root, "include_size_limit": config.include_size_limit, "run": config.run, "include_exclude": config.include_exclude, "outdir": outdir, "embed": config.embed, "dry_run": dry_run})
+}
+
+fn process_curly_tags_for_text(
+    text: &str,
     parser: &liquid::Parser,
+    globals: &liquid::Object,
 ) -> Result<String, liquid_core::Error> {
     //log::info!("process_curly_tags_for_text");
     let template = parser.parse(text)?;
 
-    let globals = liquid::object!({"items": all_pages, "branch": config.branch, "repo": config.repo , "root": root});
-
-    template.render(&globals)
+    template.render(globals)
 }
 
-pub fn check_for_invalid_curly_code(pages: &Vec<Page>) {
+pub fn check_for_invalid_curly_code(config: &Config, pages: &Vec<Page>) {
     log::info!("check_for_invalid_curly_code");
 
     for page in pages {
-        let mut in_code = false;
-        for row in page.content.split('\n') {
-            if row.starts_with("```") {
-                in_code = !in_code;
-                continue;
-            }
-            if !in_code && row.contains("{%") {
+        let (text, _code) = protect_code(&page.content, config.math);
+        for row in text.split('\n') {
+            if row.contains("{%") {
                 log::error!("Invalid curly code '{}' in '{}'", row, page.filename);
                 std::process::exit(1);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn protect_and_restore() {
+        let content =
+            "Text {{ x }} `{{ code }}` and ``a ` {% b %}``\n```\n{% raw %}\n```\n`unclosed {{ y }}";
+        let (text, code) = protect_code(content, false);
+        assert_eq!(
+            text,
+            "Text {{ x }} \u{E000}0\u{E001} and \u{E000}1\u{E001}\n\u{E000}2\u{E001}\n\u{E000}3\u{E001}\n\u{E000}4\u{E001}\n`unclosed {{ y }}"
+        );
+        assert_eq!(restore_code(&text, &code), content);
+    }
+
+    #[test]
+    fn math_blocks() {
+        let (text, code) = protect_code("$$\nx^{{2}}\n$$\n$x^{{2}}$ and `$y$`, $a\nb$", true);
+        assert_eq!(
+            text,
+            "\u{E000}3\u{E001}\n\u{E000}4\u{E001}\n\u{E000}5\u{E001}\n\u{E000}0\u{E001} and \u{E000}6\u{E001}, \u{E000}1\u{E001}\n\u{E000}2\u{E001}"
+        );
+        assert_eq!(
+            code,
+            vec!["$x^{{2}}$", "$a", "b$", "$$", "x^{{2}}", "$$", "`$y$`"]
+        );
+        // Without math the dollars are text
+        let (text, _code) = protect_code("$x^{{2}}$", false);
+        assert_eq!(text, "$x^{{2}}$");
+    }
+
+    #[test]
+    fn unclosed_tag() {
+        assert_eq!(find_unclosed_tag("a\n{% if x %}\n{% opening\ntag"), Some(3));
+        assert_eq!(find_unclosed_tag("{{ x }}\n{{ y"), Some(2));
+        assert_eq!(find_unclosed_tag("{% multi\nline %}"), None);
+    }
+
+    #[test]
+    fn error_line() {
+        let parser = liquid::ParserBuilder::with_stdlib().build().unwrap();
+        let render = |text: &str| parser.parse(text)?.render(&liquid::object!({}));
+
+        let text = "a\n\n{% if x %}\nb";
+        let err = render(text).err().unwrap();
+        assert_eq!(find_error_line(text, &err, render), Some(4));
+
+        let text = "a\n{% if true %}\n{{ nosuch }}\n{% endif %}";
+        let err = render(text).err().unwrap();
+        assert_eq!(find_error_line(text, &err, render), Some(3));
+    }
+}
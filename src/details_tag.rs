@@ -0,0 +1,128 @@
+use std::io::Write;
+
+use liquid_core::error::{ResultLiquidExt as _, ResultLiquidReplaceExt as _};
+use liquid_core::Language;
+use liquid_core::Renderable;
+use liquid_core::Result;
+use liquid_core::Runtime;
+use liquid_core::Template;
+use liquid_core::{BlockReflection, ParseBlock, TagBlock, TagTokenIter};
+
+use crate::escape;
+use crate::tag_args::{parse_bool, parse_named_options};
+
+// {% details summary="Solution" %} ... {% enddetails %}
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DetailsBlock;
+
+#[allow(clippy::missing_trait_methods)]
+impl BlockReflection for DetailsBlock {
+    fn start_tag(&self) -> &'static str {
+        "details"
+    }
+
+    fn end_tag(&self) -> &'static str {
+        "enddetails"
+    }
+
+    fn description(&self) -> &'static str {
+        ""
+    }
+}
+
+impl ParseBlock for DetailsBlock {
+    fn parse(
+        &self,
+        arguments: TagTokenIter<'_>,
+        mut block: TagBlock<'_, '_>,
+        options: &Language,
+    ) -> Result<Box<dyn Renderable>> {
+        let mut arguments = parse_named_options(arguments, "details", &["summary"], &["open"])?;
+        let open = arguments
+            .remove("open")
+            .map(|open| parse_bool("details", "open", &open))
+            .transpose()?
+            .unwrap_or(false);
+
+        let template = Template::new(block.parse_all(options).trace("{% details %}")?);
+        block.assert_empty();
+
+        Ok(Box::new(Details {
+            summary: arguments.remove("summary").unwrap_or_default(),
+            open,
+            template,
+        }))
+    }
+
+    fn reflection(&self) -> &dyn BlockReflection {
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Details {
+    summary: String,
+    open: bool,
+    template: Template,
+}
+
+#[allow(clippy::missing_trait_methods)]
+impl Renderable for Details {
+    fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
+        let body = self.template.render(runtime).trace("{% details %}")?;
+        let open = if self.open { " open" } else { "" };
+
+        // The empty lines let the body be markdown
+        write!(
+            writer,
+            "<details{open}>\n<summary>{}</summary>\n\n{}\n\n</details>",
+            escape(&self.summary),
+            body.trim_matches('\n')
+        )
+        .replace("Failed to render")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use liquid_core::parser;
+    use liquid_core::runtime::RuntimeBuilder;
+
+    fn render(text: &str) -> Result<String> {
+        let mut options = Language::default();
+        options
+            .blocks
+            .register("details".to_owned(), DetailsBlock.into());
+        let template = parser::parse(text, &options).map(Template::new)?;
+
+        let runtime = RuntimeBuilder::new().build();
+        template.render(&runtime)
+    }
+
+    #[test]
+    fn details() {
+        assert_eq!(
+            render("{% details summary=\"The <answer>\" %}\nIt is **42**.\n{% enddetails %}")
+                .unwrap(),
+            "<details>\n<summary>The &lt;answer&gt;</summary>\n\nIt is **42**.\n\n</details>"
+        );
+        assert!(
+            render("{% details summary=\"Open\" open=true %}{% enddetails %}")
+                .unwrap()
+                .starts_with("<details open>\n")
+        );
+    }
+
+    #[test]
+    fn missing_summary() {
+        let error = render("{% details %}text{% enddetails %}")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.starts_with("liquid: \"summary\" expected in details tag."));
+    }
+}
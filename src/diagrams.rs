@@ -0,0 +1,165 @@
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::fences::replace_blocks;
+use crate::{escape, ConfigDiagrams};
+
+// The languages of the fenced code blocks that are diagrams
+pub const DIAGRAMS: [&str; 2] = ["mermaid", "dot"];
+
+// Replace the ```mermaid and ```dot blocks of the markdown.
+// If there is a command for the language in the config the diagram is converted to an inline SVG,
+// otherwise it is left for the JavaScript in the footer to draw it in the browser.
+// Returns the content and the languages that need the JavaScript.
+pub fn render_diagrams(
+    content: &str,
+    config: Option<&ConfigDiagrams>,
+    math: bool,
+) -> Result<(String, Vec<String>), String> {
+    let mut client_side = vec![];
+
+    let content = replace_blocks(content, math, |block| {
+        let Some(language) = DIAGRAMS.into_iter().find(|name| *name == block.language) else {
+            return Ok(None);
+        };
+        if !block.closed {
+            return Err(format!("The ```{language} block is not closed"));
+        }
+
+        let command = config.and_then(|config| config.commands.get(language));
+        if let Some(command) = command {
+            return render_svg(language, command, &block.code).map(Some);
+        }
+        if !client_side.iter().any(|name| name == language) {
+            client_side.push(language.to_owned());
+        }
+        Ok(Some(format!(
+            r#"<pre class="diagram {language}">{}</pre>"#,
+            escape(&block.code)
+        )))
+    })?;
+
+    Ok((content, client_side))
+}
+
+// The command receives the diagram on its standard input and prints the SVG to its standard output.
+fn render_svg(language: &str, command: &str, diagram: &str) -> Result<String, String> {
+    let mut words = command.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| format!("Empty command for the {language} diagrams"))?;
+
+    log::info!("Running '{command}' to draw a {language} diagram");
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Could not run '{command}': {err}"))?;
+    // The diagram is sent from another thread while the output is read, a command that starts
+    // printing before it has read all of its input would block on a full pipe otherwise.
+    let stdin = child.stdin.take();
+    let output = thread::scope(|scope| {
+        if let Some(mut stdin) = stdin {
+            scope.spawn(move || {
+                // A command that fails may exit before reading the diagram, its status tells what happened
+                if let Err(err) = stdin.write_all(diagram.as_bytes()) {
+                    log::warn!("Could not send the diagram to '{command}': {err}");
+                }
+            });
+        }
+        child.wait_with_output()
+    })
+    .map_err(|err| format!("Could not run '{command}': {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "The command '{command}' failed to draw the {language} diagram: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (_prolog, svg) = stdout
+        .split_once("<svg")
+        .ok_or_else(|| format!("The command '{command}' did not print an SVG image"))?;
+
+    // An empty line would end the HTML block in the markdown
+    let svg = format!("<svg{svg}")
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!(r#"<div class="diagram {language}">{svg}</div>"#))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn client_side() {
+        let (html, languages) = render_diagrams(
+            "Text\n```mermaid\ngraph TD\n  A --> B\n```\n```rust\nfn main() {}\n```",
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            html,
+            "Text\n<pre class=\"diagram mermaid\">graph TD\n  A --&gt; B</pre>\n```rust\nfn main() {}\n```"
+        );
+        assert_eq!(languages, vec!["mermaid"]);
+    }
+
+    #[test]
+    fn with_command() {
+        let config = ConfigDiagrams {
+            commands: HashMap::from([(
+                String::from("dot"),
+                String::from("sed -e s/^/<svg>/ -e s/$/<\\/svg>/"),
+            )]),
+        };
+        let (html, languages) =
+            render_diagrams("```dot\ndigraph { a -> b }\n```", Some(&config), false).unwrap();
+        assert_eq!(
+            html,
+            "<div class=\"diagram dot\"><svg>digraph { a -> b }</svg></div>"
+        );
+        assert!(languages.is_empty());
+    }
+
+    #[test]
+    fn large_diagram() {
+        // More than the buffer of a pipe, `cat` prints it while it is still reading it
+        let diagram = format!("<svg>\n{}</svg>", "a -> b\n".repeat(100_000));
+        let html = render_svg("dot", "cat", &diagram).unwrap();
+        assert_eq!(
+            html.len(),
+            diagram.len() + r#"<div class="diagram dot"></div>"#.len()
+        );
+    }
+
+    #[test]
+    fn failing_command() {
+        let config = ConfigDiagrams {
+            commands: HashMap::from([(String::from("dot"), String::from("false"))]),
+        };
+        assert_eq!(
+            render_diagrams("```dot\ndigraph {}\n```", Some(&config), false)
+                .err()
+                .unwrap(),
+            "The command 'false' failed to draw the dot diagram: "
+        );
+        assert_eq!(
+            render_diagrams("```mermaid\ngraph TD", None, false)
+                .err()
+                .unwrap(),
+            "The ```mermaid block is not closed"
+        );
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use liquid_core::error::ResultLiquidReplaceExt as _;
+use liquid_core::model::Scalar;
+use liquid_core::Language;
+use liquid_core::Renderable;
+use liquid_core::Result;
+use liquid_core::Runtime;
+use liquid_core::ValueView as _;
+use liquid_core::{ParseTag, TagReflection, TagTokenIter};
+
+use crate::escape;
+use crate::tag_args::parse_named_options;
+
+const ALLOW: &str = "accelerometer; autoplay; clipboard-write; encrypted-media; gyroscope; picture-in-picture; web-share";
+
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub enum EmbedTag {
+    Vimeo,
+    PeerTube,
+    Gist,
+    Asciinema,
+    Mastodon,
+    // An iframe with a URL set in the `embed.providers` of the config
+    Embed,
+}
+
+impl EmbedTag {
+    pub const ALL: [Self; 6] = [
+        Self::Vimeo,
+        Self::PeerTube,
+        Self::Gist,
+        Self::Asciinema,
+        Self::Mastodon,
+        Self::Embed,
+    ];
+
+    fn required(self) -> &'static [&'static str] {
+        match self {
+            Self::Vimeo | Self::Gist | Self::Asciinema => &["id"],
+            Self::PeerTube => &["host", "id"],
+            Self::Mastodon => &["url"],
+            Self::Embed => &["name", "id"],
+        }
+    }
+
+    fn optional(self) -> &'static [&'static str] {
+        match self {
+            Self::Vimeo | Self::PeerTube | Self::Asciinema | Self::Embed => &["start", "title"],
+            Self::Gist => &["file"],
+            Self::Mastodon => &[],
+        }
+    }
+}
+
+#[allow(clippy::missing_trait_methods)]
+impl TagReflection for EmbedTag {
+    fn tag(&self) -> &'static str {
+        match self {
+            Self::Vimeo => "vimeo",
+            Self::PeerTube => "peertube",
+            Self::Gist => "gist",
+            Self::Asciinema => "asciinema",
+            Self::Mastodon => "mastodon",
+            Self::Embed => "embed",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        ""
+    }
+}
+
+impl ParseTag for EmbedTag {
+    fn parse(
+        &self,
+        arguments: TagTokenIter<'_>,
+        _options: &Language,
+    ) -> Result<Box<dyn Renderable>> {
+        let options = parse_named_options(arguments, self.tag(), self.required(), self.optional())?;
+
+        if let Some(start) = options.get("start") {
+            parse_start(start).map_err(liquid_core::error::Error::with_msg)?;
+        }
+        if let Some(url) = options.get("url") {
+            get_host(url).map_err(liquid_core::error::Error::with_msg)?;
+        }
+
+        Ok(Box::new(Embed {
+            kind: *self,
+            options,
+        }))
+    }
+
+    fn reflection(&self) -> &dyn TagReflection {
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Embed {
+    kind: EmbedTag,
+    options: HashMap<String, String>,
+}
+
+impl Embed {
+    fn get(&self, key: &str) -> &str {
+        self.options.get(key).map_or("", String::as_str)
+    }
+
+    fn start(&self) -> Option<u32> {
+        self.options
+            .get("start")
+            .and_then(|start| parse_start(start).ok())
+    }
+
+    fn title(&self, default: &str) -> String {
+        self.options
+            .get("title")
+            .map_or_else(|| default.to_owned(), Clone::clone)
+    }
+}
+
+#[allow(clippy::missing_trait_methods)]
+impl Renderable for Embed {
+    fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
+        let id = escape(self.get("id"));
+        let click_to_load = click_to_load(runtime);
+
+        let html = match self.kind {
+            EmbedTag::Vimeo => {
+                let start = self
+                    .start()
+                    .map_or(String::new(), |start| format!("#t={start}s"));
+                video(
+                    runtime,
+                    &format!("https://player.vimeo.com/video/{id}?dnt=1{start}"),
+                    &self.title("Vimeo video player"),
+                    None,
+                )
+            }
+            EmbedTag::PeerTube => {
+                let host = escape(
+                    self.get("host")
+                        .trim_start_matches("https://")
+                        .trim_end_matches('/'),
+                );
+                let start = self
+                    .start()
+                    .map_or(String::new(), |start| format!("?start={start}s"));
+                video(
+                    runtime,
+                    &format!("https://{host}/videos/embed/{id}{start}"),
+                    &self.title("PeerTube video player"),
+                    None,
+                )
+            }
+            EmbedTag::Gist => {
+                let link = format!("https://gist.github.com/{id}");
+                if click_to_load {
+                    format!(r#"<p><a href="{link}">View the gist on GitHub</a></p>"#)
+                } else {
+                    let file = self
+                        .options
+                        .get("file")
+                        .map_or(String::new(), |file| format!("?file={}", escape(file)));
+                    format!(
+                        r#"<script src="{link}.js{file}"></script><noscript><a href="{link}">View the gist on GitHub</a></noscript>"#
+                    )
+                }
+            }
+            EmbedTag::Asciinema => {
+                let link = format!("https://asciinema.org/a/{id}");
+                let title = escape(&self.title("asciinema recording"));
+                if click_to_load {
+                    format!(r#"<p><a href="{link}"><img src="{link}.svg" alt="{title}"></a></p>"#)
+                } else {
+                    let start = self.start().map_or(String::new(), |start| {
+                        format!(r#" data-start-at="{start}""#)
+                    });
+                    format!(
+                        r#"<script src="{link}.js" id="asciicast-{id}" async{start}></script><noscript><a href="{link}">{title}</a></noscript>"#
+                    )
+                }
+            }
+            EmbedTag::Mastodon => {
+                let url = escape(self.get("url"));
+                let host =
+                    escape(get_host(self.get("url")).map_err(liquid_core::error::Error::with_msg)?);
+                if click_to_load {
+                    format!(
+                        r#"<blockquote><a href="{url}">View the post on {host}</a></blockquote>"#
+                    )
+                } else {
+                    format!(
+                        r#"<iframe src="{url}/embed" class="mastodon-embed" style="max-width: 100%; border: 0" width="400" allowfullscreen="allowfullscreen"></iframe><script src="https://{host}/embed.js" async="async"></script>"#
+                    )
+                }
+            }
+            EmbedTag::Embed => {
+                let name = self.get("name");
+                let template = runtime
+                    .get(&[
+                        Scalar::new("embed"),
+                        Scalar::new("providers"),
+                        Scalar::new(name.to_owned()),
+                    ])
+                    .map(|value| value.to_kstr().into_string())
+                    .map_err(|_err| {
+                        liquid_core::error::Error::with_msg(format!(
+                            "Unknown embed provider '{name}'. Add it to embed.providers in config.yaml"
+                        ))
+                    })?;
+                let start = self
+                    .start()
+                    .map_or(String::new(), |start| start.to_string());
+                video(
+                    runtime,
+                    &template.replace("{id}", &id).replace("{start}", &start),
+                    &self.title(name),
+                    None,
+                )
+            }
+        };
+
+        writeln!(writer, "{html}").replace("Failed to render")?;
+
+        Ok(())
+    }
+}
+
+fn click_to_load(runtime: &dyn Runtime) -> bool {
+    runtime
+        .get(&[Scalar::new("embed"), Scalar::new("click_to_load")])
+        .is_ok_and(|value| value.query_state(liquid_core::model::State::Truthy))
+}
+
+// A responsive iframe or, if `click_to_load` is set in the config, a button that is replaced
+// by the iframe when clicked (see footer.html) so no third party is contacted before that.
+pub fn video(runtime: &dyn Runtime, src: &str, title: &str, thumbnail: Option<&str>) -> String {
+    let title = escape(title);
+    if click_to_load(runtime) {
+        let background = thumbnail.map_or(String::new(), |thumbnail| {
+            format!(" url('{}')", escape(thumbnail))
+        });
+        return format!(
+            r#"<figure class="image is-16by9"><button class="embed-placeholder has-ratio" data-src="{src}" data-allow="{ALLOW}" title="{title}" style="background: #000 center / cover no-repeat{background}; color: #fff; border: 0; cursor: pointer; font-size: 1.5rem">&#9654; {title}</button></figure>"#
+        );
+    }
+
+    format!(
+        r#"<figure class="image is-16by9"><iframe class="has-ratio" src="{src}" title="{title}" frameborder="0" allow="{ALLOW}" allowfullscreen></iframe></figure>"#
+    )
+}
+
+// "90", "1:30" and "1:01:30" are all accepted, the result is in seconds.
+pub fn parse_start(text: &str) -> core::result::Result<u32, String> {
+    text.split(':')
+        .try_fold(0, |total: u32, part| {
+            let value = part.trim().parse::<u32>().ok()?;
+            total.checked_mul(60)?.checked_add(value)
+        })
+        .filter(|_start| text.split(':').count() <= 3)
+        .ok_or_else(|| format!("Invalid start '{text}'. Expected seconds or minutes:seconds"))
+}
+
+fn get_host(url: &str) -> core::result::Result<&str, String> {
+    url.strip_prefix("https://")
+        .and_then(|rest| rest.split('/').next())
+        .filter(|host| !host.is_empty())
+        .ok_or_else(|| format!("Invalid url '{url}'. Expected https://host/..."))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use liquid_core::parser;
+    use liquid_core::runtime;
+    use liquid_core::runtime::RuntimeBuilder;
+    use liquid_core::{object, Value};
+
+    fn render(text: &str, click_to_load: bool) -> Result<String> {
+        let mut options = Language::default();
+        for tag in EmbedTag::ALL {
+            options.tags.register(tag.tag().to_string(), tag.into());
+        }
+        let template = parser::parse(text, &options).map(runtime::Template::new)?;
+
+        let runtime = RuntimeBuilder::new().build();
+        runtime.set_global(
+            "embed".into(),
+            Value::Object(object!({
+                "click_to_load": click_to_load,
+                "providers": {"loom": "https://www.loom.com/embed/{id}"},
+            })),
+        );
+        template.render(&runtime)
+    }
+
+    #[test]
+    fn vimeo() {
+        assert_eq!(
+            render(r#"{% vimeo id="76979871" start="1:30" %}"#, false).unwrap(),
+            format!(
+                "{}\n",
+                r#"<figure class="image is-16by9"><iframe class="has-ratio" src="https://player.vimeo.com/video/76979871?dnt=1#t=90s" title="Vimeo video player" frameborder="0" allow="accelerometer; autoplay; clipboard-write; encrypted-media; gyroscope; picture-in-picture; web-share" allowfullscreen></iframe></figure>"#
+            )
+        );
+    }
+
+    #[test]
+    fn peertube_click_to_load() {
+        let html = render(
+            r#"{% peertube host="framatube.org" id="abc" title="My talk" %}"#,
+            true,
+        )
+        .unwrap();
+        assert!(html.starts_with(r#"<figure class="image is-16by9"><button class="embed-placeholder has-ratio" data-src="https://framatube.org/videos/embed/abc" "#));
+        assert!(html.contains(r#"title="My talk""#));
+        assert!(!html.contains("<iframe"));
+    }
+
+    #[test]
+    fn gist_and_asciinema() {
+        assert_eq!(
+            render(r#"{% gist id="szabgab/123" file="main.rs" %}"#, false).unwrap(),
+            "<script src=\"https://gist.github.com/szabgab/123.js?file=main.rs\"></script><noscript><a href=\"https://gist.github.com/szabgab/123\">View the gist on GitHub</a></noscript>\n"
+        );
+        assert_eq!(
+            render(r#"{% asciinema id="335480" start=5 %}"#, false).unwrap(),
+            "<script src=\"https://asciinema.org/a/335480.js\" id=\"asciicast-335480\" async data-start-at=\"5\"></script><noscript><a href=\"https://asciinema.org/a/335480\">asciinema recording</a></noscript>\n"
+        );
+    }
+
+    #[test]
+    fn mastodon() {
+        assert_eq!(
+            render(
+                r#"{% mastodon url="https://fosstodon.org/@szabgab/111" %}"#,
+                true
+            )
+            .unwrap(),
+            "<blockquote><a href=\"https://fosstodon.org/@szabgab/111\">View the post on fosstodon.org</a></blockquote>\n"
+        );
+        assert_eq!(
+            render(r#"{% mastodon url="fosstodon.org/@szabgab/111" %}"#, false)
+                .err()
+                .unwrap()
+                .to_string(),
+            "liquid: Invalid url 'fosstodon.org/@szabgab/111'. Expected https://host/...\n"
+        );
+    }
+
+    #[test]
+    fn embed_by_config() {
+        assert!(render(r#"{% embed name="loom" id="xyz" %}"#, false)
+            .unwrap()
+            .contains(r#"src="https://www.loom.com/embed/xyz" title="loom""#));
+        assert_eq!(
+            render(r#"{% embed name="other" id="xyz" %}"#, false)
+                .err()
+                .unwrap()
+                .to_string(),
+            "liquid: Unknown embed provider 'other'. Add it to embed.providers in config.yaml\n"
+        );
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert_eq!(
+            render(r#"{% vimeo title="x" %}"#, false)
+                .err()
+                .unwrap()
+                .to_string(),
+            "liquid: \"id\" expected in vimeo tag.\n"
+        );
+        assert_eq!(
+            render(r#"{% vimeo id="1" autoplay=true %}"#, false)
+                .err()
+                .unwrap()
+                .to_string(),
+            "liquid: Unknown option 'autoplay' in vimeo tag. Expected one of: id, start, title\n"
+        );
+    }
+
+    #[test]
+    fn start_times() {
+        assert_eq!(parse_start("90"), Ok(90));
+        assert_eq!(parse_start("1:30"), Ok(90));
+        assert_eq!(parse_start("1:01:30"), Ok(3690));
+        assert!(parse_start("soon").is_err());
+        assert!(parse_start("1:1:1:1").is_err());
+    }
+}
@@ -0,0 +1,355 @@
+use core::ops::Range;
+use std::collections::HashMap;
+
+use markdown::mdast::{Code, Node};
+
+// A fenced code block of the markdown, ``` or ~~~ with at least three characters,
+// also the ones in list items and in block quotes.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CodeBlock {
+    // The index of the line of the opening fence and of the closing fence (or of the last line
+    // of an unclosed block) in the content
+    pub start: usize,
+    pub end: usize,
+
+    // What has to be in front of the lines replacing the block to keep them in the same list item
+    // or block quote, e.g. two spaces or "> "
+    pub indent: String,
+
+    pub language: String,
+
+    // The language and whatever follows it on the line of the opening fence, e.g. rust {3,5-7}
+    pub info: String,
+
+    // The lines between the fences without the indentation
+    pub code: String,
+
+    pub closed: bool,
+}
+
+// A link or an image of the markdown, or the definition of a reference to one
+#[derive(Debug, PartialEq, Eq)]
+pub struct Target {
+    pub url: String,
+    pub image: bool,
+
+    // The text of the link, or of the first reference link using the definition. None for the images
+    // and for the definitions no link uses.
+    pub text: Option<String>,
+
+    // The index of the line in the content
+    pub line: usize,
+}
+
+// The options of the markdown parser of the pages. With `math` the $$ blocks
+// and the $...$ spans are math, as in the pages, so the fences in them are not code blocks.
+pub fn parse_options(math: bool) -> markdown::ParseOptions {
+    markdown::ParseOptions {
+        constructs: markdown::Constructs {
+            math_text: math,
+            math_flow: math,
+            ..markdown::Constructs::gfm()
+        },
+        ..markdown::ParseOptions::gfm()
+    }
+}
+
+// All the nodes of the markdown tree, parents first
+fn descendants(content: &str, math: bool) -> Vec<Node> {
+    let Ok(tree) = markdown::to_mdast(content, &parse_options(math)) else {
+        // Only MDX can fail to parse
+        return vec![];
+    };
+    let mut nodes = vec![];
+    let mut stack = vec![tree];
+    while let Some(node) = stack.pop() {
+        // The children stay in their parent too, e.g. for the text of a link
+        stack.extend(node.children().into_iter().flatten().rev().cloned());
+        nodes.push(node);
+    }
+    nodes
+}
+
+pub fn code_blocks(content: &str, math: bool) -> Vec<CodeBlock> {
+    let lines = content.split('\n').collect::<Vec<_>>();
+    descendants(content, math)
+        .into_iter()
+        .filter_map(|node| {
+            if let Node::Code(code) = node {
+                return code_block(content, &lines, code);
+            }
+            None
+        })
+        .collect()
+}
+
+fn code_block(content: &str, lines: &[&str], code: Code) -> Option<CodeBlock> {
+    let position = code.position?;
+
+    // Indented code blocks start with the indentation instead of a fence
+    let text = content.get(position.start.offset..)?;
+    let fence = text
+        .chars()
+        .next()
+        .filter(|chr| *chr == '`' || *chr == '~')?;
+    let length = text.chars().take_while(|chr| *chr == fence).count();
+    if length < 3 {
+        return None;
+    }
+
+    let start = position.start.line - 1;
+    let end = position.end.line - 1;
+    let closing = lines[end]
+        .trim_start_matches(|chr: char| chr.is_whitespace() || chr == '>')
+        .trim_end();
+    let closed =
+        end > start && closing.chars().count() >= length && closing.chars().all(|chr| chr == fence);
+
+    let language = code.lang.unwrap_or_default();
+    let info = match code.meta {
+        Some(meta) => format!("{language} {meta}"),
+        None => language.clone(),
+    };
+    Some(CodeBlock {
+        start,
+        end,
+        indent: lines[start]
+            .chars()
+            .take(position.start.column - 1)
+            .map(|chr| if chr == '>' { '>' } else { ' ' })
+            .collect(),
+        language,
+        info,
+        code: code.value,
+        closed,
+    })
+}
+
+// For each line of the content, is it a line of a fenced code block (or of a $$ block with `math`)
+// including the fences?
+pub fn fenced_lines(content: &str, math: bool) -> Vec<bool> {
+    let lines = content.split('\n').collect::<Vec<_>>();
+    let mut fenced = vec![false; lines.len()];
+    for node in descendants(content, math) {
+        let range = if let Node::Math(block) = node {
+            block
+                .position
+                .map(|position| position.start.line - 1..position.end.line)
+        } else if let Node::Code(code) = node {
+            code_block(content, &lines, code).map(|block| block.start..block.end + 1)
+        } else {
+            None
+        };
+        if let Some(range) = range {
+            fenced[range].fill(true);
+        }
+    }
+    fenced
+}
+
+// The URLs of the links and the images, not the ones in the code. A definition is an image if
+// it is used by an image: ![alt][logo] and [logo]: images/logo.png
+pub fn targets(content: &str, math: bool) -> Vec<Target> {
+    let nodes = descendants(content, math);
+    let mut images = vec![];
+    let mut references = HashMap::new();
+    for node in &nodes {
+        if let Node::ImageReference(reference) = node {
+            images.push(reference.identifier.clone());
+        }
+        if let Node::LinkReference(reference) = node {
+            references
+                .entry(reference.identifier.clone())
+                .or_insert_with(|| node.to_string());
+        }
+    }
+
+    nodes
+        .into_iter()
+        .filter_map(|node| {
+            let link_text = matches!(node, Node::Link(_)).then(|| node.to_string());
+            let (url, image, text, position) = if let Node::Link(link) = node {
+                (link.url, false, link_text, link.position)
+            } else if let Node::Image(image) = node {
+                (image.url, true, None, image.position)
+            } else if let Node::Definition(definition) = node {
+                let image = images.contains(&definition.identifier);
+                // The first definition of a label wins
+                let text = references.remove(&definition.identifier);
+                (definition.url, image, text, definition.position)
+            } else {
+                return None;
+            };
+            Some(Target {
+                url,
+                image,
+                text,
+                line: position?.start.line - 1,
+            })
+        })
+        .collect()
+}
+
+// The byte ranges of the $...$ spans of the content
+pub fn inline_math(content: &str) -> Vec<Range<usize>> {
+    descendants(content, true)
+        .into_iter()
+        .filter_map(|node| {
+            if let Node::InlineMath(math) = node {
+                return math
+                    .position
+                    .map(|position| position.start.offset..position.end.offset);
+            }
+            None
+        })
+        .collect()
+}
+
+// Replace the lines of each code block for which `replace` returns some text. The new lines get the
+// indentation of the block so they stay in the same list item or block quote.
+pub fn replace_blocks<F>(content: &str, math: bool, mut replace: F) -> Result<String, String>
+where
+    F: FnMut(&CodeBlock) -> Result<Option<String>, String>,
+{
+    let lines = content.split('\n').collect::<Vec<_>>();
+    let mut output = vec![];
+    let mut next = 0;
+    for block in code_blocks(content, math) {
+        let Some(text) = replace(&block)? else {
+            continue;
+        };
+        output.extend(
+            lines[next..block.start]
+                .iter()
+                .map(|line| (*line).to_owned()),
+        );
+        let indent = &block.indent;
+        output.extend(text.split('\n').enumerate().map(|(index, line)| {
+            // The first line is where the opening fence was, after a list marker if there is one
+            let prefix = if index == 0 {
+                lines[block.start]
+                    .chars()
+                    .take(indent.chars().count())
+                    .collect::<String>()
+            } else {
+                indent.clone()
+            };
+            format!("{prefix}{line}")
+        }));
+        next = block.end + 1;
+    }
+    output.extend(lines[next..].iter().map(|line| (*line).to_owned()));
+    Ok(output.join("\n"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn block(start: usize, end: usize, indent: &str, info: &str, code: &str) -> CodeBlock {
+        CodeBlock {
+            start,
+            end,
+            indent: indent.to_owned(),
+            language: info.split(' ').next().unwrap().to_owned(),
+            info: info.to_owned(),
+            code: code.to_owned(),
+            closed: true,
+        }
+    }
+
+    #[test]
+    fn fences() {
+        assert_eq!(
+            code_blocks("a\n````rust {3}\n```\nx\n````\nb", false),
+            vec![block(1, 4, "", "rust {3}", "```\nx")]
+        );
+        assert_eq!(
+            code_blocks("~~~python\nx\n~~~", false),
+            vec![block(0, 2, "", "python", "x")]
+        );
+        assert_eq!(
+            code_blocks("- item\n\n  ```rust\n  let x;\n  ```\n- two", false),
+            vec![block(2, 4, "  ", "rust", "let x;")]
+        );
+        assert_eq!(
+            code_blocks("> ```\n> quoted\n> ```", false),
+            vec![block(0, 2, "> ", "", "quoted")]
+        );
+        assert_eq!(
+            code_blocks("1. ```rust\n   x\n   ```", false),
+            vec![block(0, 2, "   ", "rust", "x")]
+        );
+    }
+
+    #[test]
+    fn not_fences() {
+        // An indented code block
+        assert!(code_blocks("    ```rust\n    code", false).is_empty());
+        assert!(code_blocks("``not a fence``", false).is_empty());
+
+        let unclosed = code_blocks("```text\nx\n\ny", false);
+        assert_eq!(unclosed.len(), 1);
+        assert_eq!(unclosed[0].end, 3);
+        assert!(!unclosed[0].closed);
+        // The ``` in a longer fence doesn't close it
+        assert!(!code_blocks("````\nx\n```", false)[0].closed);
+    }
+
+    #[test]
+    fn lines() {
+        let content = "text\n```\ncode\n```\n$$\n```\n$$\nend";
+        assert_eq!(
+            fenced_lines(content, true),
+            vec![false, true, true, true, true, true, true, false]
+        );
+        // Without math the ``` in the $$ block opens a code block
+        assert_eq!(
+            fenced_lines(content, false),
+            vec![false, true, true, true, false, true, true, true]
+        );
+    }
+
+    #[test]
+    fn link_targets() {
+        let content = "[a](files/a_(1).pdf) ![b](<b c.png> \"B\")\n`[no](x.pdf)`\n\n![logo][]\n[more][m]\n\n[logo]: logo.svg\n[m]: /m.txt\n[m]: /other.txt";
+        let target = |url: &str, image, text: Option<&str>, line| Target {
+            url: url.to_owned(),
+            image,
+            text: text.map(str::to_owned),
+            line,
+        };
+        assert_eq!(
+            targets(content, false),
+            vec![
+                target("files/a_(1).pdf", false, Some("a"), 0),
+                target("b c.png", true, None, 0),
+                target("logo.svg", true, None, 6),
+                target("/m.txt", false, Some("more"), 7),
+                target("/other.txt", false, None, 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn math_spans() {
+        assert_eq!(
+            inline_math("Both $a$ and $$b\nc$$, not `$d$` or \\$e$"),
+            vec![5..8, 13..20]
+        );
+    }
+
+    #[test]
+    fn replace() {
+        let content =
+            "- item\n\n  ```rust\n  let x;\n  ```\n- ```text\n  y\n  ```\n\n```\nkept\n```";
+        let replaced = replace_blocks(content, false, |block| {
+            Ok((!block.language.is_empty()).then(|| format!("<pre>\n{}\n</pre>", block.code)))
+        })
+        .unwrap();
+        assert_eq!(
+            replaced,
+            "- item\n\n  <pre>\n  let x;\n  </pre>\n- <pre>\n  y\n  </pre>\n\n```\nkept\n```"
+        );
+    }
+}
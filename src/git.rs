@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+use crate::{stable_hash, Page};
+
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct FileHistory {
+    pub created: Option<DateTime<FixedOffset>>,
+    pub last_modified: Option<DateTime<FixedOffset>>,
+    pub contributors: Vec<String>,
+}
+
+fn git(path: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(args)
+        .output()
+        .map_err(|err| format!("Could not run git: {err}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} in '{}' failed: {}",
+            args[0],
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// The current commit and the path of the directory within the repository, e.g. site/pages/
+fn repo_position(path: &Path) -> Result<(String, String), String> {
+    let output = git(path, &["rev-parse", "HEAD", "--show-prefix"])?;
+    let mut lines = output.lines();
+    let head = lines.next().unwrap_or_default().to_owned();
+    let prefix = lines.next().unwrap_or_default().to_owned();
+    Ok((head, prefix))
+}
+
+// Run `git log` once for the whole pages directory instead of once per file
+// so even sites with thousands of pages only pay for a single git call.
+pub fn read_history(path: &Path) -> Result<HashMap<String, FileHistory>, String> {
+    log::info!("read_history of '{}'", path.display());
+
+    // -z keeps the names of the files with non-ASCII characters as they are instead of quoting them
+    let output = git(
+        path,
+        &[
+            "log",
+            "-z",
+            "--format=commit%x09%aI%x09%an",
+            "--name-only",
+            "--relative",
+            "--",
+            ".",
+        ],
+    )?;
+
+    parse_log(&output)
+}
+
+// The history only changes with a new commit so it is kept in the cache by the commit and the
+// directory of the pages. Returns the directory within the repository and the history of the files.
+fn cached_history(
+    path: &Path,
+    root: &str,
+) -> Result<(String, HashMap<String, FileHistory>), String> {
+    let (head, prefix) = repo_position(path)?;
+    let cache_file = Path::new(root).join(".cache").join("git").join(format!(
+        "{}.json",
+        stable_hash(&[head.as_bytes(), prefix.as_bytes()])
+    ));
+
+    let cached = fs::read_to_string(&cache_file)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+    if let Some(history) = cached {
+        log::info!("Using cached history of '{}'", path.display());
+        return Ok((prefix, history));
+    }
+
+    let history = read_history(path)?;
+    let saved = cache_file
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| {
+            fs::write(
+                &cache_file,
+                serde_json::to_string(&history).map_err(std::io::Error::other)?,
+            )
+        });
+    if let Err(err) = saved {
+        log::warn!(
+            "Could not save the history cache {}: {err}",
+            cache_file.display()
+        );
+    }
+    Ok((prefix, history))
+}
+
+// The log is newest first so the first commit we see of a file is its last modification
+// and the last one is its creation. With -z the fields are separated by NUL characters
+// and the list of files of each commit starts with a newline.
+fn parse_log(text: &str) -> Result<HashMap<String, FileHistory>, String> {
+    let mut history: HashMap<String, FileHistory> = HashMap::new();
+    let mut date = None;
+    let mut author = "";
+
+    for line in text.split('\0') {
+        let line = line.trim_start_matches('\n');
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(commit) = line.strip_prefix("commit\t") {
+            let (timestamp, name) = commit
+                .split_once('\t')
+                .ok_or(format!("Unexpected line in git log '{line}'"))?;
+            date = Some(
+                DateTime::parse_from_rfc3339(timestamp)
+                    .map_err(|err| format!("Invalid date '{timestamp}' in git log: {err}"))?,
+            );
+            author = name;
+            continue;
+        }
+
+        let entry = history.entry(line.to_owned()).or_default();
+        if entry.last_modified.is_none() {
+            entry.last_modified = date;
+        }
+        entry.created = date;
+        if !entry.contributors.iter().any(|name| name == author) {
+            entry.contributors.push(author.to_owned());
+        }
+    }
+
+    #[expect(clippy::iter_over_hash_type)]
+    for entry in history.values_mut() {
+        entry.contributors.reverse();
+    }
+
+    Ok(history)
+}
+
+pub fn add_history(pages: Vec<Page>, path: &Path, root: &str) -> Vec<Page> {
+    let (prefix, mut history) = match cached_history(path, root) {
+        Ok(history) => history,
+        Err(err) => {
+            log::warn!("{err}");
+            return pages;
+        }
+    };
+
+    pages
+        .into_iter()
+        .map(|mut page| {
+            page.source_path = format!("{prefix}{}", page.filename);
+            if let Some(entry) = history.remove(&page.filename) {
+                if page.updated.is_empty() {
+                    if let Some(last_modified) = entry.last_modified {
+                        page.modified = last_modified;
+                    }
+                }
+                page.created = entry.created;
+                page.last_modified = entry.last_modified;
+                page.contributors = entry.contributors;
+            }
+            page
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_git_log() {
+        let text = "commit\t2024-02-03T10:00:00+02:00\tOther Person\0\nabout.md\0index.md\0commit\t2024-01-02T10:00:00+00:00\tFoo Bar\0\nabout.md\0\0commit\t2024-01-01T10:00:00+00:00\tFoo Bar\0\nsz\u{e9}p.md\0";
+        let history = parse_log(text).unwrap();
+
+        assert_eq!(
+            history["about.md"],
+            FileHistory {
+                created: Some(DateTime::parse_from_rfc3339("2024-01-02T10:00:00+00:00").unwrap()),
+                last_modified: Some(
+                    DateTime::parse_from_rfc3339("2024-02-03T10:00:00+02:00").unwrap()
+                ),
+                contributors: vec!["Foo Bar".to_string(), "Other Person".to_string()],
+            }
+        );
+        assert_eq!(
+            history["index.md"].created,
+            history["index.md"].last_modified
+        );
+        assert_eq!(history["index.md"].contributors, vec!["Other Person"]);
+        assert_eq!(history["sz\u{e9}p.md"].contributors, vec!["Foo Bar"]);
+    }
+
+    #[test]
+    fn history_of_the_pages() {
+        let root = tempdir::TempDir::new("git").unwrap();
+        let root = root.path().to_str().unwrap();
+        let pages = vec![Page {
+            filename: String::from("about.md"),
+            ..Page::default()
+        }];
+        let path = Path::new("test_cases/demo/pages");
+
+        let pages = add_history(pages, path, root);
+        assert_eq!(pages[0].source_path, "test_cases/demo/pages/about.md");
+        assert!(pages[0].last_modified.is_some());
+        let cached = fs::read_dir(Path::new(root).join(".cache/git"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(cached.len(), 1);
+
+        // The second time the history comes from the cache
+        fs::write(
+            &cached[0],
+            r#"{"about.md": {"created": null, "last_modified": null, "contributors": ["From Cache"]}}"#,
+        )
+        .unwrap();
+        let again = add_history(pages, path, root);
+        assert_eq!(again[0].contributors, vec!["From Cache"]);
+    }
+
+    #[test]
+    fn parse_invalid_git_log() {
+        let error = parse_log("commit\tyesterday\tFoo Bar\0\nabout.md\0")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            "Invalid date 'yesterday' in git log: input contains invalid characters"
+        );
+    }
+}
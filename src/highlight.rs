@@ -0,0 +1,309 @@
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+use crate::diagrams::DIAGRAMS;
+use crate::fences::replace_blocks;
+use crate::ConfigHighlight;
+
+// The prefix keeps the classes of syntect from clashing with the classes of Bulma (e.g. tag, number)
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "sx-" };
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+// Replace the fenced code blocks of the markdown by highlighted HTML.
+// The info string can contain the lines to highlight: ```rust {3,5-7}
+// A language of `diff` or `diff-rust` marks the added and removed lines.
+// An unclosed block runs to the end of the document, just as in markdown.
+// The ```math blocks and the diagrams are left alone.
+pub fn highlight_code_blocks(
+    content: &str,
+    config: &ConfigHighlight,
+    math: bool,
+) -> Result<String, String> {
+    replace_blocks(content, math, |block| {
+        // They are converted to MathML and to diagrams
+        if block.language == "math" || DIAGRAMS.contains(&block.language.as_str()) {
+            return Ok(None);
+        }
+        highlight_block(&block.code, &block.info, config).map(Some)
+    })
+}
+
+fn highlight_block(code: &str, info: &str, config: &ConfigHighlight) -> Result<String, String> {
+    let (language, ranges) = match info.split_once('{') {
+        Some((language, ranges)) => (language.trim(), parse_line_ranges(ranges)?),
+        None => (info, vec![]),
+    };
+
+    let (language, diff) = match language.strip_prefix("diff-") {
+        Some(language) => (language, true),
+        None => (language, language == "diff"),
+    };
+
+    let syntax = SYNTAX_SET
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+
+    let mut generator =
+        ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .map_err(|err| format!("Could not highlight {language} code: {err}"))?;
+    }
+    let html = generator.finalize();
+
+    let lines = split_lines(&html)
+        .into_iter()
+        .zip(code.split('\n'))
+        .enumerate()
+        .map(|(index, (html, text))| {
+            let number = index + 1;
+            let mut classes = vec!["line"];
+            if ranges
+                .iter()
+                .any(|(start, end)| *start <= number && number <= *end)
+            {
+                classes.push("line-highlight");
+            }
+            if diff && text.starts_with('+') {
+                classes.push("line-added");
+            }
+            if diff && text.starts_with('-') {
+                classes.push("line-removed");
+            }
+            let number = if config.line_numbers {
+                format!(r#"<span class="line-number">{number}</span>"#)
+            } else {
+                String::new()
+            };
+            format!(
+                r#"<span class="{}">{number}{html}</span>"#,
+                classes.join(" ")
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let class = if language.is_empty() {
+        String::from("sx-code")
+    } else {
+        format!("sx-code language-{language}")
+    };
+
+    Ok(format!(
+        r#"<pre class="highlight"><code class="{class}">{lines}</code></pre>"#
+    ))
+}
+
+// "3,5-7}" => [(3, 3), (5, 7)]
+fn parse_line_ranges(text: &str) -> Result<Vec<(usize, usize)>, String> {
+    let text = text.trim().trim_end_matches('}');
+    let error = || format!("Invalid line numbers '{{{text}}}' in code block");
+
+    text.split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (start, end) = part.split_once('-').unwrap_or((part, part));
+            let start = start.trim().parse::<usize>().map_err(|_err| error())?;
+            let end = end.trim().parse::<usize>().map_err(|_err| error())?;
+            if end < start {
+                return Err(error());
+            }
+            Ok((start, end))
+        })
+        .collect()
+}
+
+// Split the highlighted HTML into lines. Spans that are still open at the end of a line
+// (e.g. in a multi-line comment) are closed and opened again on the next line.
+fn split_lines(html: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut open: Vec<&str> = vec![];
+    let mut line = String::new();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if rest.starts_with("<span") {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            let (tag, after) = rest.split_at(end);
+            open.push(tag);
+            line += tag;
+            rest = after;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("</span>") {
+            open.pop();
+            line += "</span>";
+            rest = after;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix('\n') {
+            line += &"</span>".repeat(open.len());
+            lines.push(line);
+            line = open.concat();
+            rest = after;
+            continue;
+        }
+
+        let end = rest.find(['<', '\n']).unwrap_or(rest.len()).max(1);
+        let (text, after) = rest.split_at(end);
+        line += text;
+        rest = after;
+    }
+    lines.push(line);
+
+    lines
+}
+
+pub fn render_css(config: &ConfigHighlight, outdir: &str) -> Result<(), String> {
+    log::info!("render syntax highlighting css");
+
+    let mut css = theme_css(&config.theme)?;
+    if !config.dark_theme.is_empty() {
+        let dark = theme_css(&config.dark_theme)?;
+        css = format!("{css}@media (prefers-color-scheme: dark) {{\n{dark}\n}}\n");
+    }
+    css += include_str!("../templates/highlight.css");
+
+    let path = Path::new(outdir).join("syntax.css");
+    fs::write(&path, css).map_err(|err| format!("Could not write {}: {err}", path.display()))
+}
+
+fn theme_css(name: &str) -> Result<String, String> {
+    let theme = THEME_SET.themes.get(name).ok_or_else(|| {
+        let mut names = THEME_SET.themes.keys().cloned().collect::<Vec<String>>();
+        names.sort();
+        format!(
+            "Unknown highlight theme '{name}'. Available themes: {}",
+            names.join(", ")
+        )
+    })?;
+
+    css_for_theme_with_class_style(theme, CLASS_STYLE)
+        .map_err(|err| format!("Could not create css for theme '{name}': {err}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> ConfigHighlight {
+        ConfigHighlight {
+            theme: String::from("InspiredGitHub"),
+            dark_theme: String::new(),
+            line_numbers: false,
+        }
+    }
+
+    #[test]
+    fn line_ranges() {
+        assert_eq!(parse_line_ranges("3,5-7}").unwrap(), vec![(3, 3), (5, 7)]);
+        assert_eq!(parse_line_ranges(" 2 }").unwrap(), vec![(2, 2)]);
+        assert_eq!(
+            parse_line_ranges("7-5}").err().unwrap(),
+            "Invalid line numbers '{7-5}' in code block"
+        );
+    }
+
+    #[test]
+    fn split_open_spans() {
+        assert_eq!(
+            split_lines(
+                r#"<span class="a">one
+two</span> three"#
+            ),
+            vec![
+                r#"<span class="a">one</span>"#,
+                r#"<span class="a">two</span> three"#
+            ]
+        );
+    }
+
+    #[test]
+    fn highlight_rust() {
+        let content = "Text\n```rust {2}\nfn main() {\n    println!(\"<hi>\");\n}\n```\nMore text";
+        let html = highlight_code_blocks(content, &config(), false).unwrap();
+        assert!(html.starts_with(
+            "Text\n<pre class=\"highlight\"><code class=\"sx-code language-rust\"><span class=\"line\">"
+        ));
+        assert!(html.ends_with("</code></pre>\nMore text"));
+        assert!(html.contains(r#"<span class="line line-highlight">"#));
+        assert!(html.contains("&lt;hi&gt;"));
+        assert!(html.contains("sx-storage sx-type sx-function sx-rust"));
+        assert_eq!(html.matches(r#"<span class="line"#).count(), 3);
+    }
+
+    #[test]
+    fn highlight_diff_with_line_numbers() {
+        let mut config = config();
+        config.line_numbers = true;
+        let html = highlight_code_blocks(
+            "```diff-python\n x = 1\n-y = 2\n+y = 3\n```",
+            &config,
+            false,
+        )
+        .unwrap();
+        assert!(html.contains(r#"<span class="line"><span class="line-number">1</span>"#));
+        assert!(
+            html.contains(r#"<span class="line line-removed"><span class="line-number">2</span>"#)
+        );
+        assert!(
+            html.contains(r#"<span class="line line-added"><span class="line-number">3</span>"#)
+        );
+    }
+
+    #[test]
+    fn other_fences() {
+        // A ``` line inside a ```` block is part of the code
+        let html = highlight_code_blocks(
+            "````markdown\n```rust\nfn main() {}\n```\n````\nAfter",
+            &config(),
+            false,
+        )
+        .unwrap();
+        assert_eq!(html.matches(r#"<span class="line"#).count(), 3);
+        assert!(html.ends_with("</code></pre>\nAfter"));
+        assert!(!html.contains("````"));
+
+        let html = highlight_code_blocks("~~~python\nx = 1\n~~~", &config(), false).unwrap();
+        assert!(
+            html.starts_with(r#"<pre class="highlight"><code class="sx-code language-python">"#)
+        );
+        assert!(html.ends_with("</code></pre>"));
+
+        // In a list item the block keeps the indentation of the item
+        let html = highlight_code_blocks(
+            "- Item\n\n  ```rust\n  let x = 1;\n  ```\n- Next",
+            &config(),
+            false,
+        )
+        .unwrap();
+        assert!(html.starts_with("- Item\n\n  <pre class=\"highlight\">"));
+        assert!(html.ends_with("</code></pre>\n- Next"));
+        assert!(!html.contains("```"));
+    }
+
+    #[test]
+    fn skip_math_and_diagrams() {
+        let content = "```math\nx^2\n```\n```mermaid\ngraph TD\n```\n```dot\ndigraph {}\n```";
+        assert_eq!(
+            highlight_code_blocks(content, &config(), true).unwrap(),
+            content
+        );
+    }
+
+    #[test]
+    fn unknown_theme() {
+        let error = theme_css("no-such-theme").err().unwrap();
+        assert!(error.starts_with("Unknown highlight theme 'no-such-theme'. Available themes: "));
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use image::codecs::avif::AvifEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::{stable_hash, ConfigImages};
+
+// The HTML the markdown crate creates for ![alt](src) and ![alt](src "title")
+static IMG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<img src="([^"]*)"([^>]*?) ?/>"#).unwrap());
+
+// GIF files are left alone as they might be animated
+const RESIZABLE: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+
+    // The widths of the resized copies in each format, the last one is the width of the original.
+    // A format is left out if its copy of the original size is larger than the source file.
+    pub webp: Vec<u32>,
+    pub avif: Vec<u32>,
+}
+
+// Create the resized WebP (and AVIF) copies of the images next to the originals in the outdir.
+// The copies are kept in the cache by the hash of the source so unchanged images are not processed again.
+pub fn process_images(
+    config: &ConfigImages,
+    root: &str,
+    outdir: &str,
+    paths: &[PathBuf],
+) -> Result<HashMap<String, Image>, String> {
+    log::info!("process_images");
+
+    let mut images = HashMap::new();
+    for path in paths {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if !RESIZABLE.contains(&extension.as_str()) {
+            continue;
+        }
+
+        let source = Path::new(root).join(path);
+        let content = fs::read(&source)
+            .map_err(|err| format!("Could not read image {:?}: {err}", source.display()))?;
+        let cache_dir = cache_dir(config, root, &content);
+
+        let image = if let Some(image) = read_cache(&cache_dir) {
+            log::info!("Using cached images of '{}'", path.display());
+            image
+        } else {
+            log::info!("Resizing '{}'", path.display());
+            resize(config, &content, &cache_dir)
+                .map_err(|err| format!("Could not process image {:?}: {err}", path.display()))?
+        };
+
+        let src = path.to_string_lossy().into_owned();
+        for (width, format) in variants(&image) {
+            let destination = Path::new(outdir).join(variant_path(&src, width, format));
+            if let Some(dir) = destination.parent() {
+                fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+            }
+            fs::copy(cache_dir.join(format!("{width}.{format}")), &destination)
+                .map_err(|err| format!("Could not copy to {:?}: {err}", destination.display()))?;
+        }
+        images.insert(src, image);
+    }
+
+    Ok(images)
+}
+
+fn cache_dir(config: &ConfigImages, root: &str, content: &[u8]) -> PathBuf {
+    let settings = format!("{:?} {} {}", config.widths, config.avif, config.quality);
+    Path::new(root)
+        .join(&config.cache)
+        .join("images")
+        .join(stable_hash(&[settings.as_bytes(), content]))
+}
+
+// The image.json is written last so it is only there if all the copies are
+fn read_cache(dir: &Path) -> Option<Image> {
+    let content = fs::read_to_string(dir.join("image.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn resize(config: &ConfigImages, content: &[u8], cache_dir: &Path) -> Result<Image, String> {
+    let original = image::load_from_memory(content).map_err(|err| err.to_string())?;
+    let (width, height) = (original.width(), original.height());
+
+    // Never enlarge the image
+    let mut widths = config
+        .widths
+        .iter()
+        .copied()
+        .filter(|size| *size < width)
+        .chain([width])
+        .collect::<Vec<_>>();
+    widths.sort_unstable();
+    widths.dedup();
+
+    fs::create_dir_all(cache_dir).map_err(|err| err.to_string())?;
+    let mut webp = vec![];
+    let mut avif = vec![];
+    for size in &widths {
+        // The encoders only accept 8 bit pixels
+        let resized = if *size == width {
+            original.to_rgba8()
+        } else {
+            original
+                .resize(*size, u32::MAX, FilterType::Lanczos3)
+                .to_rgba8()
+        };
+
+        let webp_file = webp::Encoder::from_rgba(&resized, resized.width(), resized.height())
+            .encode_simple(false, f32::from(config.quality))
+            .map_err(|err| format!("Could not encode WebP: {err:?}"))?;
+        if webp_file.len() < content.len() {
+            let path = cache_dir.join(format!("{size}.webp"));
+            fs::write(&path, &*webp_file)
+                .map_err(|err| format!("Could not write {:?}: {err}", path.display()))?;
+            webp.push(*size);
+        }
+
+        if config.avif {
+            let mut avif_file = vec![];
+            DynamicImage::ImageRgba8(resized)
+                .write_with_encoder(AvifEncoder::new_with_speed_quality(
+                    &mut avif_file,
+                    8,
+                    config.quality,
+                ))
+                .map_err(|err| err.to_string())?;
+            if avif_file.len() < content.len() {
+                let path = cache_dir.join(format!("{size}.avif"));
+                fs::write(&path, &avif_file)
+                    .map_err(|err| format!("Could not write {:?}: {err}", path.display()))?;
+                avif.push(*size);
+            }
+        }
+    }
+
+    // Without the copy of the original size the browser would show a smaller one enlarged
+    if webp.last() != Some(&width) {
+        webp.clear();
+    }
+    if avif.last() != Some(&width) {
+        avif.clear();
+    }
+
+    let image = Image {
+        width,
+        height,
+        webp,
+        avif,
+    };
+    let json = serde_json::to_string_pretty(&image).map_err(|err| err.to_string())?;
+    fs::write(cache_dir.join("image.json"), json).map_err(|err| err.to_string())?;
+
+    Ok(image)
+}
+
+fn variants(image: &Image) -> Vec<(u32, &'static str)> {
+    image
+        .webp
+        .iter()
+        .map(|width| (*width, "webp"))
+        .chain(image.avif.iter().map(|width| (*width, "avif")))
+        .collect()
+}
+
+// examples/files/cat.jpg -> examples/files/cat-480.webp
+fn variant_path(src: &str, width: u32, extension: &str) -> String {
+    let (dir, filename) = src
+        .rsplit_once('/')
+        .map_or(("", src), |(dir, filename)| (dir, filename));
+    let stem = filename
+        .rsplit_once('.')
+        .map_or(filename, |(stem, _extension)| stem);
+    let variant = format!("{stem}-{width}.{extension}");
+    if src.contains('/') {
+        format!("{dir}/{variant}")
+    } else {
+        variant
+    }
+}
+
+// Replace the <img> tags of the processed images by a <picture> offering the resized copies.
+pub fn add_srcset<S: core::hash::BuildHasher>(
+    html: &str,
+    config: &ConfigImages,
+    images: &HashMap<String, Image, S>,
+) -> String {
+    IMG.replace_all(html, |captures: &Captures| {
+        let src = &captures[1];
+        let Some(image) = images.get(src.trim_start_matches('/')) else {
+            return captures[0].to_owned();
+        };
+
+        let sources = [("avif", &image.avif), ("webp", &image.webp)]
+            .into_iter()
+            .filter(|(_extension, widths)| !widths.is_empty())
+            .map(|(extension, widths)| {
+                let srcset = widths
+                    .iter()
+                    .map(|width| format!("{} {width}w", variant_path(src, *width, extension)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    r#"<source type="image/{extension}" srcset="{srcset}" sizes="{}" />"#,
+                    config.sizes
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!(
+            r#"<picture>{}<img src="{src}"{} width="{}" height="{}" loading="lazy" decoding="async" /></picture>"#,
+            sources.concat(),
+            &captures[2],
+            image.width,
+            image.height
+        )
+    })
+    .into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(cache: &str, avif: bool) -> ConfigImages {
+        ConfigImages {
+            widths: vec![16, 32, 100],
+            sizes: String::from("(max-width: 600px) 100vw, 600px"),
+            avif,
+            quality: 70,
+            cache: cache.to_owned(),
+        }
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            variant_path("examples/files/cat.jpg", 480, "webp"),
+            "examples/files/cat-480.webp"
+        );
+        assert_eq!(variant_path("/cat.min.png", 16, "avif"), "/cat.min-16.avif");
+        assert_eq!(variant_path("cat", 16, "webp"), "cat-16.webp");
+    }
+
+    #[test]
+    fn resize_and_cache() {
+        let root = tempdir::TempDir::new("images").unwrap();
+        let root_str = root.path().to_str().unwrap();
+        let outdir = root.path().join("out");
+        let outdir_str = outdir.to_str().unwrap();
+        fs::create_dir(root.path().join("img")).unwrap();
+        // Noise, so the PNG is larger than the WebP and AVIF copies
+        image::RgbImage::from_fn(40, 20, |x, y| {
+            let value = u8::try_from((x * 7919 + y * 104_729) % 251).unwrap();
+            image::Rgb([value, 255 - value, value / 2])
+        })
+        .save(root.path().join("img/noise.png"))
+        .unwrap();
+
+        let config = config(".cache", true);
+        let paths = [
+            PathBuf::from("img/noise.png"),
+            PathBuf::from("img/anim.gif"),
+        ];
+        let images = process_images(&config, root_str, outdir_str, &paths).unwrap();
+        let expected = Image {
+            width: 40,
+            height: 20,
+            webp: vec![16, 32, 40],
+            avif: vec![16, 32, 40],
+        };
+        assert_eq!(images.get("img/noise.png"), Some(&expected));
+        assert_eq!(images.len(), 1);
+
+        let small = image::open(outdir.join("img/noise-16.webp")).unwrap();
+        assert_eq!((small.width(), small.height()), (16, 8));
+        assert!(outdir.join("img/noise-40.avif").exists());
+
+        // The second run takes the copies from the cache
+        fs::remove_dir_all(&outdir).unwrap();
+        let cache = cache_dir(
+            &config,
+            root_str,
+            &fs::read(root.path().join("img/noise.png")).unwrap(),
+        );
+        fs::write(cache.join("16.webp"), "cached").unwrap();
+        process_images(&config, root_str, outdir_str, &paths).unwrap();
+        assert_eq!(
+            fs::read_to_string(outdir.join("img/noise-16.webp")).unwrap(),
+            "cached"
+        );
+    }
+
+    #[test]
+    fn larger_copies_are_dropped() {
+        let root = tempdir::TempDir::new("images").unwrap();
+        let root_str = root.path().to_str().unwrap();
+        let outdir = root.path().join("out");
+        fs::create_dir(root.path().join("img")).unwrap();
+        // The small PNG of a single color is smaller than the AVIF copies, but not than the WebP ones
+        image::RgbImage::from_pixel(40, 20, image::Rgb([200, 10, 10]))
+            .save(root.path().join("img/red.png"))
+            .unwrap();
+
+        let images = process_images(
+            &config(".cache", true),
+            root_str,
+            outdir.to_str().unwrap(),
+            &[PathBuf::from("img/red.png")],
+        )
+        .unwrap();
+        assert_eq!(
+            images.get("img/red.png"),
+            Some(&Image {
+                width: 40,
+                height: 20,
+                webp: vec![16, 32, 40],
+                avif: vec![],
+            })
+        );
+        let source = fs::metadata(root.path().join("img/red.png")).unwrap().len();
+        assert!(fs::metadata(outdir.join("img/red-40.webp")).unwrap().len() < source);
+        assert!(!outdir.join("img/red-40.avif").exists());
+    }
+
+    #[test]
+    fn srcset() {
+        let images = HashMap::from([(
+            String::from("img/red.png"),
+            Image {
+                width: 40,
+                height: 20,
+                webp: vec![16, 40],
+                avif: vec![],
+            },
+        )]);
+        let html = add_srcset(
+            r#"<p><img src="img/red.png" alt="Red" title="A red box" /> <img src="other.gif" alt="" /></p>"#,
+            &config(".cache", false),
+            &images,
+        );
+        assert_eq!(
+            html,
+            r#"<p><picture><source type="image/webp" srcset="img/red-16.webp 16w, img/red-40.webp 40w" sizes="(max-width: 600px) 100vw, 600px" /><img src="img/red.png" alt="Red" title="A red box" width="40" height="20" loading="lazy" decoding="async" /></picture> <img src="other.gif" alt="" /></p>"#
+        );
+    }
+}
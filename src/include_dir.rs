@@ -0,0 +1,285 @@
+use std::fs;
+use std::fs::File;
+use std::io::Write as _;
+use std::path::Path;
+
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+#[derive(Debug, PartialEq)]
+pub struct Listing {
+    // The files and directories drawn like the output of the `tree` command, line by line
+    pub tree: Vec<String>,
+    // The text files to show, relative to the root of the site
+    pub files: Vec<String>,
+    // All the files, relative to the root of the site
+    pub all_files: Vec<String>,
+}
+
+// Walk the directory skipping everything that matches one of the exclude patterns
+// or one of the patterns in the .gitignore file of the directory.
+pub fn read_dir(root: &Path, dir: &str, exclude: &[String]) -> Result<Listing, String> {
+    let path = root.join(dir);
+    if !path.is_dir() {
+        return Err(format!("Directory {:?} not found", path.display()));
+    }
+
+    let mut patterns = exclude.to_vec();
+    if let Ok(gitignore) = fs::read_to_string(path.join(".gitignore")) {
+        patterns.extend(
+            gitignore
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+                .map(str::to_owned),
+        );
+    }
+
+    let name = Path::new(dir).file_name().map_or_else(
+        || dir.to_owned(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let mut listing = Listing {
+        tree: vec![format!("{name}/")],
+        files: vec![],
+        all_files: vec![],
+    };
+    walk(root, dir, "", "", &patterns, &mut listing)?;
+
+    Ok(listing)
+}
+
+fn walk(
+    root: &Path,
+    dir: &str,
+    relative: &str,
+    prefix: &str,
+    patterns: &[String],
+    listing: &mut Listing,
+) -> Result<(), String> {
+    let path = root.join(dir).join(relative);
+    let mut entries = fs::read_dir(&path)
+        .map_err(|err| format!("Could not read directory {:?}: {err}", path.display()))?
+        .filter_map(Result::ok)
+        // The symlinks to directories are skipped, they could point back to one of their parents
+        .filter_map(|entry| {
+            let file_type = entry.file_type().ok()?;
+            if file_type.is_symlink() && entry.path().is_dir() {
+                return None;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let entry_path = if relative.is_empty() {
+                name.clone()
+            } else {
+                format!("{relative}/{name}")
+            };
+            Some((name, entry_path, file_type.is_dir()))
+        })
+        .filter(|(_name, entry_path, is_dir)| !is_excluded(entry_path, *is_dir, patterns))
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    let count = entries.len();
+    for (index, (name, entry_path, is_dir)) in entries.into_iter().enumerate() {
+        let last = index + 1 == count;
+        let connector = if last { "└── " } else { "├── " };
+
+        if is_dir {
+            listing.tree.push(format!("{prefix}{connector}{name}/"));
+            let prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+            walk(root, dir, &entry_path, &prefix, patterns, listing)?;
+            continue;
+        }
+
+        listing.tree.push(format!("{prefix}{connector}{name}"));
+        let file = format!("{dir}/{entry_path}");
+        let is_text =
+            fs::read(root.join(&file)).is_ok_and(|content| String::from_utf8(content).is_ok());
+        if is_text {
+            listing.files.push(file.clone());
+        }
+        listing.all_files.push(file);
+    }
+
+    Ok(())
+}
+
+// A subset of the .gitignore rules: `*` and `?` wildcards, a trailing `/` to match only directories
+// and patterns containing a `/` are matched against the path from the top of the directory.
+pub fn is_excluded(path: &str, is_dir: bool, patterns: &[String]) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+
+    patterns.iter().any(|pattern| {
+        let (pattern, dir_only) = match pattern.strip_suffix('/') {
+            Some(pattern) => (pattern, true),
+            None => (pattern.as_str(), false),
+        };
+        if dir_only && !is_dir {
+            return false;
+        }
+        if pattern.contains('/') {
+            return glob_match(pattern.trim_start_matches('/'), path);
+        }
+        glob_match(pattern, name)
+    })
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    // matches[j] is true if the pattern so far matches the first j characters of the text
+    let mut matches = vec![false; text.len() + 1];
+    matches[0] = true;
+    for chr in pattern {
+        let mut next = vec![false; text.len() + 1];
+        for index in 0..=text.len() {
+            next[index] = match chr {
+                '*' => matches[index] || (index > 0 && next[index - 1] && text[index - 1] != '/'),
+                '?' => index > 0 && matches[index - 1] && text[index - 1] != '/',
+                _ => index > 0 && matches[index - 1] && text[index - 1] == chr,
+            };
+        }
+        matches = next;
+    }
+
+    matches[text.len()]
+}
+
+// examples/project -> examples/project.zip
+pub fn zip_path(dir: &str) -> String {
+    format!("{}.zip", dir.trim_end_matches('/'))
+}
+
+// Save the files in a zip file at the same path in the output directory as the directory in the source.
+pub fn create_zip(
+    root: &Path,
+    dir: &str,
+    files: &[String],
+    outdir: &str,
+) -> Result<String, String> {
+    let zip_path = zip_path(dir);
+    let path = Path::new(outdir).join(&zip_path);
+    log::info!("create zip file {}", path.display());
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("Could not create directory {:?}: {err}", parent.display()))?;
+    }
+    let output = File::create(&path)
+        .map_err(|err| format!("Could not create {:?}: {err}", path.display()))?;
+
+    // Inside the zip file everything is in a directory called as the directory we include.
+    let parent = Path::new(dir)
+        .parent()
+        .map(|parent| parent.display().to_string())
+        .unwrap_or_default();
+
+    let mut zip = ZipWriter::new(output);
+    for file in files {
+        let name = file
+            .strip_prefix(&parent)
+            .unwrap_or(file)
+            .trim_start_matches('/');
+        let content =
+            fs::read(root.join(file)).map_err(|err| format!("Could not read {file:?}: {err}"))?;
+        zip.start_file(name, SimpleFileOptions::default())
+            .map_err(|err| format!("Could not add {name} to {}: {err}", path.display()))?;
+        zip.write_all(&content)
+            .map_err(|err| format!("Could not add {name} to {}: {err}", path.display()))?;
+    }
+    zip.finish()
+        .map_err(|err| format!("Could not save {}: {err}", path.display()))?;
+
+    Ok(zip_path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exclude_patterns() {
+        let patterns = vec![
+            String::from("target/"),
+            String::from("*.log"),
+            String::from("/src/generated.rs"),
+        ];
+        assert!(is_excluded("target", true, &patterns));
+        assert!(!is_excluded("target", false, &patterns));
+        assert!(is_excluded("logs/build.log", false, &patterns));
+        assert!(is_excluded("src/generated.rs", false, &patterns));
+        assert!(!is_excluded("generated.rs", false, &patterns));
+        assert!(!is_excluded("src/main.rs", false, &patterns));
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(glob_match("ma?n.*", "main.rs"));
+        assert!(!glob_match("*.rs", "main.rs.bak"));
+        assert!(!glob_match("src/*", "src/a/b.rs"));
+    }
+
+    #[test]
+    fn tree() {
+        let listing = read_dir(
+            Path::new("test_cases/demo"),
+            "examples/project",
+            &[String::from("target")],
+        )
+        .unwrap();
+        assert_eq!(
+            listing.tree.join("\n"),
+            "project/\n├── .gitignore\n├── Cargo.toml\n└── src/\n    └── main.rs"
+        );
+        assert_eq!(
+            listing.files,
+            vec![
+                "examples/project/.gitignore",
+                "examples/project/Cargo.toml",
+                "examples/project/src/main.rs"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loop() {
+        let dir = tempdir::TempDir::new("loop").unwrap();
+        let project = dir.path().join("project");
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        std::os::unix::fs::symlink(&project, project.join("src").join("again")).unwrap();
+        std::os::unix::fs::symlink(project.join("src").join("main.rs"), project.join("link.rs"))
+            .unwrap();
+
+        let listing = read_dir(dir.path(), "project", &[]).unwrap();
+        assert_eq!(
+            listing.tree.join("\n"),
+            "project/\n├── link.rs\n└── src/\n    └── main.rs"
+        );
+    }
+
+    #[test]
+    fn zip_file() {
+        let dir = tempdir::TempDir::new("zip").unwrap();
+        let outdir = dir.path().to_str().unwrap();
+        let root = Path::new("test_cases/demo");
+        let listing = read_dir(root, "examples/project", &[]).unwrap();
+        let zip_path = create_zip(root, "examples/project", &listing.all_files, outdir).unwrap();
+        assert_eq!(zip_path, "examples/project.zip");
+
+        let archive = zip::ZipArchive::new(File::open(dir.path().join(zip_path)).unwrap()).unwrap();
+        let mut names = archive.file_names().collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            vec![
+                "project/.gitignore",
+                "project/Cargo.toml",
+                "project/src/main.rs"
+            ]
+        );
+    }
+}
@@ -1,18 +1,20 @@
 use std::collections::HashMap;
 use std::io::Write;
 use std::path::Path;
+use std::sync::LazyLock;
 
 use liquid_core::error::ResultLiquidReplaceExt as _;
 use liquid_core::model::Scalar;
-use liquid_core::parser::TryMatchToken;
 use liquid_core::Language;
 use liquid_core::Renderable;
 use liquid_core::Result;
 use liquid_core::Runtime;
 use liquid_core::ValueView as _;
 use liquid_core::{ParseTag, TagReflection, TagTokenIter};
+use regex::Regex;
 
-use crate::{read_languages, read_no_extension};
+use crate::tag_args::{parse_bool, parse_options};
+use crate::{include_dir, output_tag, read_languages, read_no_extension};
 
 #[derive(Copy, Clone, Debug, Default)]
 pub struct IncludeTag;
@@ -31,30 +33,65 @@ impl TagReflection for IncludeTag {
 impl ParseTag for IncludeTag {
     fn parse(
         &self,
-        mut arguments: TagTokenIter<'_>,
+        arguments: TagTokenIter<'_>,
         _options: &Language,
     ) -> Result<Box<dyn Renderable>> {
         println!("Parsing include tag");
-        arguments
-            .expect_next("\"file\" expected.")?
-            .expect_str("file")
-            .into_result_custom_msg("\"file\" expected.")?;
-
-        arguments
-            .expect_next("Assignment operator \"=\" expected.")?
-            .expect_str("=")
-            .into_result_custom_msg("Assignment operator \"=\" expected.")?;
-
-        let token = arguments.expect_next("Identifier or value expected")?;
-        let file = match token.expect_literal() {
-            TryMatchToken::Matches(name) => name.to_kstr().into_string(),
-            TryMatchToken::Fails(name) => return name.raise_error().into_err(),
-        };
 
-        arguments.expect_nothing()?;
-        //println!("Parsing done");
+        let mut file = None;
+        let mut include = Include::new(String::new());
+
+        for (key, value) in parse_options(arguments, "include", &["dedent", "run", "zip"])? {
+            match key.as_str() {
+                "file" => file = Some(value),
+                "dir" => include.dir = Some(value),
+                "exclude" => {
+                    include.exclude = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|pattern| !pattern.is_empty())
+                        .map(str::to_owned)
+                        .collect();
+                }
+                "zip" => include.zip = parse_bool("include", &key, &value)?,
+                "lines" => {
+                    include.lines = Some(
+                        parse_lines(&value).map_err(liquid_core::error::Error::with_msg)?,
+                    );
+                }
+                "region" => include.region = Some(value),
+                "dedent" => include.dedent = parse_bool("include", &key, &value)?,
+                "title" => include.title = Some(value),
+                "link" => include.link = parse_bool("include", &key, &value)?,
+                "lang" => include.lang = Some(value),
+                "run" => include.run = parse_bool("include", &key, &value)?,
+                _ => {
+                    return Err(liquid_core::error::Error::with_msg(format!(
+                        "Unknown option '{key}' in include tag. Expected one of: file, dir, lines, region, dedent, title, link, lang, run, exclude, zip"
+                    )))
+                }
+            }
+        }
 
-        Ok(Box::new(Include { file }))
+        if let Some(dir) = &include.dir {
+            if file.is_some() {
+                return Err(liquid_core::error::Error::with_msg(format!(
+                    "Only one of 'file' and 'dir' can be used in the include tag of '{dir}'"
+                )));
+            }
+            return Ok(Box::new(include));
+        }
+
+        include.file =
+            file.ok_or_else(|| liquid_core::error::Error::with_msg("\"file\" expected."))?;
+        if include.lines.is_some() && include.region.is_some() {
+            return Err(liquid_core::error::Error::with_msg(format!(
+                "Only one of 'lines' and 'region' can be used in the include tag of '{}'",
+                include.file
+            )));
+        }
+
+        Ok(Box::new(include))
     }
 
     fn reflection(&self) -> &dyn TagReflection {
@@ -62,16 +99,355 @@ impl ParseTag for IncludeTag {
     }
 }
 
+// "10-25" => (10, 25), "7" => (7, 7)
+fn parse_lines(text: &str) -> Result<(usize, usize), String> {
+    let error =
+        || format!("Invalid lines '{text}' in include tag. Expected a range like \"10-25\"");
+    let (start, end) = text.split_once('-').unwrap_or((text, text));
+    let start = start.trim().parse::<usize>().map_err(|_err| error())?;
+    let end = end.trim().parse::<usize>().map_err(|_err| error())?;
+    if start == 0 || end < start {
+        return Err(error());
+    }
+    Ok((start, end))
+}
+
+#[derive(Debug, PartialEq)]
+enum Marker<'text> {
+    Region(&'text str),
+    EndRegion,
+}
+
+// Lines like `// region: setup`, `# endregion` or `<!-- region: setup -->`
+fn region_marker(line: &str) -> Option<Marker<'_>> {
+    static RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r"^\s*(?://+|#+|--|;+|<!--|/\*)\s*(?:region:\s*([\w.]+(?:-[\w.]+)*)|endregion\b)",
+        )
+        .unwrap()
+    });
+    RE.captures(line).map(|capture| {
+        capture
+            .get(1)
+            .map_or(Marker::EndRegion, |name| Marker::Region(name.as_str()))
+    })
+}
+
+fn select_lines(content: &str, (start, end): (usize, usize)) -> Result<Vec<&str>, String> {
+    let lines = content.lines().collect::<Vec<_>>();
+    if lines.len() < end {
+        return Err(format!(
+            "lines {start}-{end} requested but the file has only {} lines",
+            lines.len()
+        ));
+    }
+    Ok(lines
+        .into_iter()
+        .skip(start - 1)
+        .take(end - start + 1)
+        .collect())
+}
+
+// Returns the lines between the `region: name` and the matching `endregion` markers
+// and the line number of the first one.
+fn select_region<'text>(
+    content: &'text str,
+    name: &str,
+) -> Result<(usize, Vec<&'text str>), String> {
+    let mut start = None;
+    let mut lines = vec![];
+    let mut depth = 0;
+
+    for (index, line) in content.lines().enumerate() {
+        let marker = region_marker(line);
+        let Some(first) = start else {
+            if marker == Some(Marker::Region(name)) {
+                start = Some(index + 2);
+            }
+            continue;
+        };
+        match marker {
+            Some(Marker::Region(_)) => depth += 1,
+            Some(Marker::EndRegion) if depth == 0 => return Ok((first, lines)),
+            Some(Marker::EndRegion) => depth -= 1,
+            None => lines.push(line),
+        }
+    }
+
+    match start {
+        Some(_) => Err(format!("region '{name}' has no endregion")),
+        None => Err(format!("region '{name}' not found")),
+    }
+}
+
+// Remove the indentation common to all the non-empty lines
+fn dedent(lines: &[&str]) -> Vec<String> {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.chars().take_while(|chr| chr.is_whitespace()).count())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| line.chars().skip(indent).collect())
+        .collect()
+}
+
 #[derive(Debug)]
+#[expect(clippy::struct_excessive_bools)]
 struct Include {
     file: String,
+    lines: Option<(usize, usize)>,
+    region: Option<String>,
+    dedent: bool,
+    title: Option<String>,
+    link: bool,
+    lang: Option<String>,
+    run: bool,
+    dir: Option<String>,
+    exclude: Vec<String>,
+    zip: bool,
+}
+
+impl Include {
+    fn new(file: String) -> Self {
+        Self {
+            file,
+            lines: None,
+            region: None,
+            dedent: false,
+            title: None,
+            link: true,
+            lang: None,
+            run: false,
+            dir: None,
+            exclude: vec![],
+            zip: false,
+        }
+    }
+
+    fn get_language(&self, path: &Path) -> Result<String> {
+        if let Some(lang) = &self.lang {
+            return Ok(lang.clone());
+        }
+
+        let ext_to_language: HashMap<String, String> = read_languages();
+        let no_extension: HashMap<String, String> = read_no_extension();
+
+        let file_name = path
+            .file_name()
+            .ok_or(liquid_core::error::Error::with_msg(format!(
+                "file_name not found in include tag '{}'",
+                self.file
+            )))?
+            .to_str()
+            .ok_or(liquid_core::error::Error::with_msg(format!(
+                "file_name could not convert to string in include tag '{}'",
+                self.file
+            )))?;
+
+        // TODO remove the hard coded mapping of .gitignore
+        // TODO properly handle files that do not have an extension
+        if no_extension.contains_key(file_name) {
+            return Ok(no_extension[file_name].clone());
+        }
+
+        let extension = path
+            .extension()
+            .ok_or(liquid_core::error::Error::with_msg(format!(
+                "extension not found in include tag '{}'",
+                self.file
+            )))?
+            .to_str()
+            .ok_or(liquid_core::error::Error::with_msg(format!(
+                "extension could not convert to string in include tag {}",
+                self.file
+            )))?;
+
+        //println!("extension: {extension}");
+        let extension = extension.to_lowercase();
+
+        if ext_to_language.contains_key(&extension) {
+            Ok(ext_to_language[&extension].clone())
+        } else {
+            Err(liquid_core::error::Error::with_msg(format!(
+                "Unhandled extension '{extension}' in {}",
+                self.file
+            )))
+        }
+    }
+
+    // The code to show and the range of lines it was taken from
+    fn get_code(&self, file_content: &str) -> Result<(String, Option<(usize, usize)>), String> {
+        let (range, lines) = if let Some(range) = self.lines {
+            (Some(range), select_lines(file_content, range)?)
+        } else if let Some(region) = &self.region {
+            let (start, lines) = select_region(file_content, region)?;
+            (Some((start, start + lines.len().saturating_sub(1))), lines)
+        } else {
+            (None, file_content.lines().collect())
+        };
+
+        let lines = lines
+            .into_iter()
+            .filter(|line| region_marker(line).is_none())
+            .collect::<Vec<_>>();
+
+        let mut code = if self.dedent {
+            dedent(&lines).join("\n")
+        } else {
+            lines.join("\n")
+        };
+
+        // Keep the end of the file as it is when including the whole file
+        if range.is_none() && file_content.ends_with('\n') {
+            code.push('\n');
+        }
+
+        Ok((code, range))
+    }
+
+    fn render_file(
+        &self,
+        writer: &mut dyn Write,
+        runtime: &dyn Runtime,
+        root: &str,
+        repo: &str,
+        branch: &str,
+    ) -> Result<()> {
+        let path = Path::new(&self.file);
+        let include_path = Path::new(&root).join(path);
+
+        let language = self.get_language(path)?;
+
+        let file_content = std::fs::read_to_string(&include_path)
+            .replace(format!("Failed to read file {:?}", include_path.display()))?;
+
+        if let Ok(limit) = runtime.get(&[Scalar::new("include_size_limit")]) {
+            let limit = limit
+                .as_scalar()
+                .and_then(|limit| limit.to_integer())
+                .unwrap_or(i64::MAX);
+            let size = i64::try_from(file_content.len()).unwrap_or(i64::MAX);
+            if size > limit {
+                log::warn!(
+                    "The included file '{}' is {size} bytes, more than the include_size_limit of {limit} bytes",
+                    self.file
+                );
+            }
+        }
+
+        let (code, range) = self.get_code(&file_content).map_err(|err| {
+            liquid_core::error::Error::with_msg(format!("{err} in include tag '{}'", self.file))
+        })?;
+
+        let title = self
+            .title
+            .clone()
+            .unwrap_or_else(|| path.display().to_string());
+        let header = if self.link {
+            let anchor = range.map_or(String::new(), |(start, end)| format!("#L{start}-L{end}"));
+            format!(
+                "**[{title}]({}/tree/{}/{}{anchor})**\n",
+                repo,
+                branch,
+                path.display()
+            )
+        } else if self.title.is_some() {
+            format!("**{title}**\n")
+        } else {
+            String::new()
+        };
+
+        write!(writer, "{header}```{language}\n{code}\n```\n").replace("Failed to render")?;
+
+        if self.run {
+            let output = output_tag::run_example(runtime, root, &self.file, None, None)?;
+            write!(writer, "{output}").replace("Failed to render")?;
+        }
+
+        Ok(())
+    }
+
+    // A tree of the files in the directory followed by the content of each file.
+    fn render_dir(
+        &self,
+        writer: &mut dyn Write,
+        runtime: &dyn Runtime,
+        dir: &str,
+        root: &str,
+        repo: &str,
+        branch: &str,
+    ) -> Result<()> {
+        let mut exclude = runtime
+            .get(&[Scalar::new("include_exclude")])
+            .ok()
+            .and_then(|value| {
+                value.as_array().map(|patterns| {
+                    patterns
+                        .values()
+                        .map(|pattern| pattern.to_kstr().into_string())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .unwrap_or_default();
+        exclude.extend(self.exclude.iter().cloned());
+
+        let listing = include_dir::read_dir(Path::new(root), dir, &exclude)
+            .map_err(|err| liquid_core::error::Error::with_msg(format!("{err} in include tag")))?;
+
+        let title = self.title.clone().unwrap_or_else(|| dir.to_owned());
+        let header = if self.link {
+            format!("**[{title}]({repo}/tree/{branch}/{dir})**\n")
+        } else {
+            format!("**{title}**\n")
+        };
+        write!(
+            writer,
+            "{header}```text\n{}\n```\n",
+            listing.tree.join("\n")
+        )
+        .replace("Failed to render")?;
+
+        if self.zip && !output_tag::is_dry_run(runtime) {
+            let outdir = runtime
+                .get(&[Scalar::new("outdir")])
+                .map(|value| value.to_kstr().into_string())
+                .map_err(|_err| {
+                    liquid_core::error::Error::with_msg(
+                        "No value called 'outdir' was passed to the render function.",
+                    )
+                })?;
+            let zip_path =
+                include_dir::create_zip(Path::new(root), dir, &listing.all_files, &outdir)
+                    .map_err(liquid_core::error::Error::with_msg)?;
+            let name = zip_path.rsplit('/').next().unwrap_or(&zip_path);
+            write!(writer, "\n[Download {name}](/{zip_path})\n").replace("Failed to render")?;
+        }
+
+        for file in listing.files {
+            let mut include = Self::new(file.clone());
+            include.link = self.link;
+            include.title = Some(file);
+            // Don't fail on the files we don't know, just show them as text
+            include.lang = Some(
+                include
+                    .get_language(Path::new(&include.file))
+                    .unwrap_or_else(|_err| String::from("text")),
+            );
+            writeln!(writer).replace("Failed to render")?;
+            include.render_file(writer, runtime, root, repo, branch)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[allow(clippy::missing_trait_methods)]
 impl Renderable for Include {
     fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
-        let ext_to_language: HashMap<String, String> = read_languages();
-        let no_extension: HashMap<String, String> = read_no_extension();
         //println!("render_to");
 
         let root = match runtime.get(&[Scalar::new("root")]) {
@@ -101,66 +477,11 @@ impl Renderable for Include {
             }
         };
 
-        let path = Path::new(&self.file);
-        let include_path = Path::new(&root).join(path);
-
-        let file_name = path
-            .file_name()
-            .ok_or(liquid_core::error::Error::with_msg(format!(
-                "file_name not found in include tag '{}'",
-                self.file
-            )))?
-            .to_str()
-            .ok_or(liquid_core::error::Error::with_msg(format!(
-                "file_name could not convert to string in include tag '{}'",
-                self.file
-            )))?;
-
-        // TODO remove the hard coded mapping of .gitignore
-        // TODO properly handle files that do not have an extension
-        let language = if no_extension.contains_key(file_name) {
-            &no_extension[file_name]
-        } else {
-            let extension = path
-                .extension()
-                .ok_or(liquid_core::error::Error::with_msg(format!(
-                    "extension not found in include tag '{}'",
-                    self.file
-                )))?
-                .to_str()
-                .ok_or(liquid_core::error::Error::with_msg(format!(
-                    "extension could not convert to string in include tag {}",
-                    self.file
-                )))?;
-
-            //println!("extension: {extension}");
-            let extension = extension.to_lowercase();
-
-            if ext_to_language.contains_key(&extension) {
-                ext_to_language[&extension].as_str()
-            } else {
-                return Err(liquid_core::error::Error::with_msg(format!(
-                    "Unhandled extension '{extension}' in {}",
-                    self.file
-                )));
-            }
-        };
-
-        let file_content = std::fs::read_to_string(&include_path)
-            .replace(format!("Failed to read file {:?}", include_path.display()))?;
-        write!(
-            writer,
-            "**[{}]({}/tree/{}/{})**\n```{}\n{}\n```\n",
-            path.display(),
-            &repo,
-            &branch,
-            path.display(),
-            language,
-            &file_content
-        )
-        .replace("Failed to render")?;
+        if let Some(dir) = &self.dir {
+            return self.render_dir(writer, runtime, dir, &root, &repo, &branch);
+        }
 
-        Ok(())
+        self.render_file(writer, runtime, &root, &repo, &branch)
     }
 }
 
@@ -250,4 +571,87 @@ mod test {
             "liquid: Unhandled extension 'qqrq' in test_cases/other.qqrq\n"
         );
     }
+
+    fn render(text: &str) -> Result<String> {
+        let template = parser::parse(text, &options()).map(runtime::Template::new)?;
+
+        let runtime = RuntimeBuilder::new().build();
+        runtime.set_global("root".into(), Value::scalar("test_cases/demo"));
+        runtime.set_global(
+            "repo".into(),
+            Value::scalar("https://github.com/szabgab/code-maven.rs"),
+        );
+        runtime.set_global("branch".into(), Value::scalar("main"));
+
+        template.render(&runtime)
+    }
+
+    #[test]
+    fn include_lines() {
+        let output = render(r#"{% include file="examples/regions.rs" lines="14-15" %}"#).unwrap();
+        assert_eq!(output, "**[examples/regions.rs](https://github.com/szabgab/code-maven.rs/tree/main/examples/regions.rs#L14-L15)**\n```rust\n    println!(\"{counter:?}\");\n}\n```\n");
+    }
+
+    #[test]
+    fn include_region_dedent() {
+        let output =
+            render(r#"{% include file="examples/regions.rs" region="count" dedent %}"#).unwrap();
+        assert_eq!(output, "**[examples/regions.rs](https://github.com/szabgab/code-maven.rs/tree/main/examples/regions.rs#L10-L10)**\n```rust\n*counter.entry(word).or_insert(0) += 1;\n```\n");
+    }
+
+    #[test]
+    fn include_without_region_markers() {
+        let output = render(r#"{% include file="examples/regions.rs" link=false %}"#).unwrap();
+        assert!(output.starts_with("```rust\nuse std::collections::HashMap;\n"));
+        assert!(!output.contains("region"));
+    }
+
+    #[test]
+    fn include_title_and_lang() {
+        let output = render(
+            r#"{% include file="examples/demo.yaml" title="Config" link=false lang="text" %}"#,
+        )
+        .unwrap();
+        assert_eq!(output, "**Config**\n```text\nfield: value\n\n```\n");
+    }
+
+    #[test]
+    fn include_invalid_options() {
+        assert_eq!(
+            render(r#"{% include file="examples/regions.rs" region="nope" %}"#)
+                .err()
+                .unwrap()
+                .to_string(),
+            "liquid: region 'nope' not found in include tag 'examples/regions.rs'\n"
+        );
+        assert_eq!(
+            render(r#"{% include file="examples/regions.rs" lines="10-99" %}"#)
+                .err()
+                .unwrap()
+                .to_string(),
+            "liquid: lines 10-99 requested but the file has only 15 lines in include tag 'examples/regions.rs'\n"
+        );
+        assert_eq!(
+            render(r#"{% include file="examples/regions.rs" size=3 %}"#)
+                .err()
+                .unwrap()
+                .to_string(),
+            "liquid: Unknown option 'size' in include tag. Expected one of: file, dir, lines, region, dedent, title, link, lang, run, exclude, zip\n"
+        );
+    }
+
+    #[test]
+    fn dedent_lines() {
+        assert_eq!(dedent(&["    a", "", "      b"]), vec!["a", "", "  b"]);
+    }
+
+    #[test]
+    fn include_dir() {
+        let output =
+            render(r#"{% include dir="examples/project" exclude="*.toml" link=false %}"#).unwrap();
+        assert_eq!(
+            output,
+            "**examples/project**\n```text\nproject/\n├── .gitignore\n└── src/\n    └── main.rs\n```\n\n**examples/project/.gitignore**\n```gitignore\n/target\n*.log\n\n```\n\n**examples/project/src/main.rs**\n```rust\nfn main() {\n    println!(\"Hello from the project\");\n}\n\n```\n"
+        );
+    }
 }
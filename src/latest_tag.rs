@@ -1,9 +1,11 @@
 use std::io::Write;
 
+use chrono::{DateTime, NaiveDate};
+
 use liquid_core::error::ResultLiquidReplaceExt as _;
 use liquid_core::model::Scalar;
-use liquid_core::parser::TryMatchToken;
 use liquid_core::Language;
+use liquid_core::ObjectView;
 use liquid_core::Renderable;
 use liquid_core::Result;
 use liquid_core::Runtime;
@@ -11,6 +13,9 @@ use liquid_core::ValueView as _;
 use liquid_core::{ParseTag, TagReflection, TagTokenIter};
 use serde::Serialize;
 
+use crate::escape;
+use crate::tag_args::parse_named_options;
+
 #[derive(Copy, Clone, Debug, Default)]
 pub struct LatestTag;
 
@@ -28,54 +33,82 @@ impl TagReflection for LatestTag {
 impl ParseTag for LatestTag {
     fn parse(
         &self,
-        mut arguments: TagTokenIter<'_>,
+        arguments: TagTokenIter<'_>,
         _options: &Language,
     ) -> Result<Box<dyn Renderable>> {
-        arguments
-            .expect_next("limit expected")?
-            .expect_str("limit")
-            .into_result_custom_msg("limit expected.")?;
-
-        arguments
-            .expect_next("Assignment operator \"=\" expected.")?
-            .expect_str("=")
-            .into_result_custom_msg("Assignment operator \"=\" expected.")?;
-
-        let limit_token = arguments.expect_next("Identifier or value expected")?;
-        let limit_value = match limit_token.expect_literal() {
-            TryMatchToken::Matches(name) => name.to_kstr().to_string(),
-            TryMatchToken::Fails(name) => return name.raise_error().into_err(),
+        let mut options = parse_named_options(
+            arguments,
+            "latest",
+            &["limit"],
+            &[
+                "tag",
+                "tags",
+                "match",
+                "exclude_tag",
+                "author",
+                "type",
+                "since",
+                "offset",
+                "sort",
+                "format",
+                "related",
+            ],
+        )?;
+
+        let mut tags = options
+            .remove("tag")
+            .map(|tag| vec![tag])
+            .unwrap_or_default();
+        tags.extend(split_list(options.remove("tags")));
+
+        let all = match options.remove("match").as_deref() {
+            None | Some("any") => false,
+            Some("all") => true,
+            Some(value) => return Err(invalid_value("match", value, "any or all")),
         };
-        let Ok(limit) = limit_value.parse::<u8>() else {
-            return Err(liquid_core::error::Error::with_msg("Expected number"));
+
+        let since = options
+            .remove("since")
+            .map(|since| {
+                NaiveDate::parse_from_str(&since, "%Y-%m-%d")
+                    .map_err(|_err| invalid_value("since", &since, "a date as YYYY-MM-DD"))
+            })
+            .transpose()?;
+
+        let related = match options.remove("related").as_deref() {
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(value) => return Err(invalid_value("related", value, "true or false")),
         };
 
-        let key = arguments.next();
-
-        let tag = match key {
-            Some(tag_token) => {
-                tag_token
-                    .expect_str("tag")
-                    .into_result_custom_msg("expected tag")?;
-                arguments
-                    .expect_next("Assignment operator \"=\" expected.")?
-                    .expect_str("=")
-                    .into_result_custom_msg("Assignment operator \"=\" expected.")?;
-
-                let literal = arguments.expect_next("value of tag")?.expect_literal();
-
-                let tag_value = match literal {
-                    TryMatchToken::Matches(name) => name.to_kstr().to_string(),
-                    TryMatchToken::Fails(name) => return name.raise_error().into_err(),
-                };
-                Some(tag_value)
-            }
+        // The related pages are listed by their relevance unless they are sorted otherwise
+        let sort = match options.remove("sort").as_deref() {
             None => None,
+            Some("date") => Some(Sort::Date),
+            Some("title") => Some(Sort::Title),
+            Some(value) => return Err(invalid_value("sort", value, "date or title")),
         };
 
-        arguments.expect_nothing()?;
+        let format = match options.remove("format").as_deref() {
+            None | Some("list") => Format::List,
+            Some("cards") => Format::Cards,
+            Some("table") => Format::Table,
+            Some(value) => return Err(invalid_value("format", value, "list, cards or table")),
+        };
 
-        Ok(Box::new(Latest { limit, tag }))
+        Ok(Box::new(Latest {
+            limit: parse_number("limit", options.remove("limit"))?,
+            offset: parse_number("offset", options.remove("offset"))?,
+            tags,
+            all,
+            exclude_tags: split_list(options.remove("exclude_tag")),
+            author: options.remove("author"),
+            page_type: options.remove("type"),
+            since,
+            related,
+            sort,
+            format,
+        }))
     }
 
     fn reflection(&self) -> &dyn TagReflection {
@@ -83,72 +116,290 @@ impl ParseTag for LatestTag {
     }
 }
 
+// "rust, web" => ["rust", "web"]
+fn split_list(value: Option<String>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn parse_number(key: &str, value: Option<String>) -> Result<usize> {
+    value.map_or(Ok(0), |value| {
+        value
+            .parse::<usize>()
+            .map_err(|_err| invalid_value(key, &value, "a number"))
+    })
+}
+
+fn invalid_value(key: &str, value: &str, expected: &str) -> liquid_core::Error {
+    liquid_core::error::Error::with_msg(format!(
+        "Invalid value '{value}' for '{key}' in latest tag. Expected {expected}"
+    ))
+}
+
+#[derive(Debug)]
+enum Sort {
+    Date,
+    Title,
+}
+
+#[derive(Debug)]
+enum Format {
+    List,
+    Cards,
+    Table,
+}
+
 #[derive(Debug)]
 struct Latest {
-    limit: u8,
-    tag: Option<String>,
+    limit: usize,
+    offset: usize,
+    // The pages need to have any (or all) of these tags
+    tags: Vec<String>,
+    all: bool,
+    exclude_tags: Vec<String>,
+    author: Option<String>,
+    page_type: Option<String>,
+    since: Option<NaiveDate>,
+    // Only the related pages of the current page
+    related: bool,
+    sort: Option<Sort>,
+    format: Format,
+}
+
+#[derive(Debug)]
+struct Entry {
+    title: String,
+    url_path: String,
+    description: String,
+    // YYYY-MM-DD
+    date: String,
+}
+
+impl Latest {
+    fn select(&self, item: &dyn ObjectView) -> Result<Option<Entry>> {
+        let get_str = |key: &str| {
+            item.get(key)
+                .map(|value| value.to_kstr().into_string())
+                .unwrap_or_default()
+        };
+        let get_list = |key: &str| {
+            item.get(key)
+                .and_then(|value| {
+                    value.as_array().map(|values| {
+                        values
+                            .values()
+                            .map(|val| val.to_kstr().into_string())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .unwrap_or_default()
+        };
+
+        let title = item
+            .get("title")
+            .ok_or_else(|| liquid_core::error::Error::with_msg("title is missing"))?
+            .to_kstr()
+            .into_string();
+        let url_path = item
+            .get("url_path")
+            .ok_or_else(|| liquid_core::error::Error::with_msg("url_path is missing"))?
+            .to_kstr()
+            .into_string();
+        if url_path == "archive" {
+            return Ok(None);
+        }
+
+        let tags = get_list("tags");
+        let has_tag = |tag: &String| tags.contains(tag);
+        if !self.tags.is_empty() {
+            let selected = if self.all {
+                self.tags.iter().all(has_tag)
+            } else {
+                self.tags.iter().any(has_tag)
+            };
+            if !selected {
+                return Ok(None);
+            }
+        }
+        if self.exclude_tags.iter().any(has_tag) {
+            return Ok(None);
+        }
+        if self
+            .author
+            .as_ref()
+            .is_some_and(|author| *author != get_str("author"))
+        {
+            return Ok(None);
+        }
+        if self
+            .page_type
+            .as_ref()
+            .is_some_and(|page_type| !get_list("types").contains(page_type))
+        {
+            return Ok(None);
+        }
+
+        let date = DateTime::parse_from_rfc3339(&get_str("date")).ok();
+        if let Some(since) = self.since {
+            if date.is_none_or(|date| date.date_naive() < since) {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(Entry {
+            title,
+            url_path,
+            description: get_str("description"),
+            date: date
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+        }))
+    }
 }
 
 #[allow(clippy::missing_trait_methods)]
 impl Renderable for Latest {
     fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
-        let mut count = 0;
-
-        let selected_tag = self.tag.clone().unwrap_or_default();
-
-        match runtime.get(&[Scalar::new("items")]) {
-            // Ok(values) => values.as_array().unwrap().values().collect::<Vec<_>>(),
-            Ok(values) => {
-                log::info!("Find latest items");
-                for value in values
-                    .as_array()
-                    .ok_or(liquid_core::error::Error::with_msg(
-                        "Expected to be an array",
-                    ))?
-                    .values()
-                {
-                    let obj = value
-                        .as_object()
-                        .ok_or(liquid_core::error::Error::with_msg("Expected arrayObject"))?;
-                    let title = obj
-                        .get("title")
-                        .ok_or(liquid_core::error::Error::with_msg("title is missing"))?
-                        .to_kstr()
-                        .to_string();
-                    let url_path = obj
-                        .get("url_path")
-                        .ok_or(liquid_core::error::Error::with_msg("url_path is missing"))?
-                        .to_kstr()
-                        .to_string();
-                    let tags = obj
-                        .get("tags")
-                        .ok_or(liquid_core::error::Error::with_msg("Expected tags"))?
-                        .as_array()
-                        .ok_or(liquid_core::error::Error::with_msg("Expected array"))?
-                        .values()
-                        .map(|val| val.to_kstr().to_string())
-                        .collect::<Vec<_>>();
-                    if self.tag.is_some() && !tags.contains(&selected_tag) {
-                        continue;
-                    }
-                    if url_path == "archive" {
-                        continue;
-                    }
-
-                    writeln!(writer, "* [{title}](/{url_path})").replace("Failed to render")?;
-                    count += 1;
-                    if count >= self.limit {
-                        break;
-                    }
-                }
+        let items = runtime.get(&[Scalar::new("items")]).map_err(|_err| {
+            liquid_core::error::Error::with_msg(
+                "No value called 'items' was passed to the render function.",
+            )
+        })?;
+        let items = items.as_array().ok_or_else(|| {
+            liquid_core::error::Error::with_msg("Expected 'items' to be an array")
+        })?;
+
+        log::info!("Find latest items");
+        let related = if self.related {
+            Some(related_paths(runtime, items)?)
+        } else {
+            None
+        };
+        let mut entries = vec![];
+        for value in items.values() {
+            let item = value.as_object().ok_or_else(|| {
+                liquid_core::error::Error::with_msg("Expected 'items' to contain objects")
+            })?;
+            if let Some(entry) = self.select(item)? {
+                entries.push(entry);
             }
-            Err(_) => return Err(liquid_core::error::Error::with_msg("Expected number")),
+        }
+        if let Some(related) = related {
+            entries.retain(|entry| related.contains(&entry.url_path));
+            entries.sort_by_key(|entry| related.iter().position(|path| *path == entry.url_path));
         }
 
+        match self.sort {
+            Some(Sort::Title) => entries.sort_by(|first, second| {
+                first.title.to_lowercase().cmp(&second.title.to_lowercase())
+            }),
+            // The dates are YYYY-MM-DD, the newest first
+            Some(Sort::Date) => entries.sort_by(|first, second| second.date.cmp(&first.date)),
+            // The items are already sorted by date, the newest first
+            None => {}
+        }
+        let entries = entries
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit)
+            .collect::<Vec<_>>();
+
+        let output = match self.format {
+            Format::List => entries
+                .iter()
+                .map(|entry| format!("* [{}](/{})\n", entry.title, entry.url_path))
+                .collect::<Vec<_>>()
+                .concat(),
+            Format::Table => table(&entries),
+            Format::Cards => cards(&entries),
+        };
+        write!(writer, "{output}").replace("Failed to render")?;
+
         Ok(())
     }
 }
 
+// The url_path of the related pages of the current page, the most related first
+fn related_paths(
+    runtime: &dyn Runtime,
+    items: &dyn liquid_core::model::ArrayView,
+) -> Result<Vec<String>> {
+    let url_path = runtime
+        .get(&[Scalar::new("url_path")])
+        .map_err(|_err| {
+            liquid_core::error::Error::with_msg(
+                "No value called 'url_path' was passed to the render function.",
+            )
+        })?
+        .to_kstr()
+        .into_string();
+
+    let page = items
+        .values()
+        .filter_map(|value| value.as_object())
+        .find(|item| {
+            item.get("url_path")
+                .is_some_and(|path| path.to_kstr() == url_path.as_str())
+        });
+    Ok(page
+        .and_then(|page| page.get("related"))
+        .and_then(|related| related.as_array())
+        .map(|related| {
+            related
+                .values()
+                .filter_map(|value| value.as_object())
+                .filter_map(|item| item.get("url_path"))
+                .map(|path| path.to_kstr().into_string())
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+fn table(entries: &[Entry]) -> String {
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "| [{}](/{}) | {} | {} |\n",
+                entry.title.replace('|', "\\|"),
+                entry.url_path,
+                entry.date,
+                entry.description.replace('|', "\\|")
+            )
+        })
+        .collect::<Vec<_>>()
+        .concat();
+    format!("| Title | Date | Description |\n| --- | --- | --- |\n{rows}")
+}
+
+// Bulma cards without empty lines so markdown leaves the HTML as it is
+fn cards(entries: &[Entry]) -> String {
+    let cards = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                r#"<div class="column is-one-third"><div class="card"><div class="card-content">
+<p class="title is-5"><a href="/{}">{}</a></p>
+<p class="subtitle is-6">{}</p>
+<p>{}</p>
+</div></div></div>
+"#,
+                entry.url_path,
+                escape(&entry.title),
+                entry.date,
+                escape(&entry.description)
+            )
+        })
+        .collect::<Vec<_>>()
+        .concat();
+    format!("<div class=\"columns is-multiline latest\">\n{cards}</div>\n")
+}
+
 #[derive(Debug, Serialize)]
 pub struct Item<'aa> {
     title: &'aa str,
@@ -293,4 +544,137 @@ mod test {
             "* [one](/1)\n* [three](/3)\n* [five](/5)\n* [seven](/7)\n* [nine](/9)\n"
         );
     }
+
+    // The items with a date (the newest first), an author, a type and a description
+    fn render(text: &str) -> Result<String> {
+        let template = parser::parse(text, &options()).map(runtime::Template::new)?;
+        let runtime = RuntimeBuilder::new().build();
+
+        let objects = get_items()
+            .iter()
+            .enumerate()
+            .map(|(index, item)| {
+                let date = format!("2023-10-{:02}T12:00:00Z", 20 - index);
+                let author = if index % 2 == 0 { "foo" } else { "bar" };
+                let types = if index < 3 { vec!["video"] } else { vec![] };
+                let description = format!("About {} | more", item.title);
+                Value::Object(object!({
+                    "title": item.title,
+                    "url_path": item.url_path,
+                    "tags": item.tags,
+                    "date": date,
+                    "author": author,
+                    "types": types,
+                    "description": description,
+                }))
+            })
+            .collect::<Vec<_>>();
+        runtime.set_global("items".into(), Value::Array(objects));
+
+        template.render(&runtime)
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(
+            render(r#"{% latest limit=3 tags="web, programming" exclude_tag="programming" %}"#)
+                .unwrap(),
+            "* [one](/1)\n* [three](/3)\n* [five](/5)\n"
+        );
+        assert_eq!(
+            render(r#"{% latest limit=3 tags="web, programming" match="all" %}"#).unwrap(),
+            ""
+        );
+        assert_eq!(
+            render(r#"{% latest limit=10 author="bar" type="video" %}"#).unwrap(),
+            "* [two](/2)\n"
+        );
+        assert_eq!(
+            render(r#"{% latest limit=10 since="2023-10-18" %}"#).unwrap(),
+            "* [one](/1)\n* [two](/2)\n* [three](/3)\n"
+        );
+    }
+
+    #[test]
+    fn sort_and_offset() {
+        assert_eq!(
+            render(r#"{% latest limit=3 offset=2 sort="title" %}"#).unwrap(),
+            "* [four](/4)\n* [nine](/9)\n* [one](/1)\n"
+        );
+    }
+
+    #[test]
+    fn related() {
+        let template = parser::parse(
+            r#"{% latest limit=2 related="true" %} {% latest limit=5 related="true" sort="title" %}"#,
+            &options(),
+        )
+        .map(runtime::Template::new)
+        .unwrap();
+        let runtime = RuntimeBuilder::new().build();
+        let related = ["7", "2", "5"].map(|path| object!({"title": "", "url_path": path}));
+        let objects = get_items()
+            .iter()
+            .map(|item| {
+                let mut obj =
+                    object!({"title": item.title, "url_path": item.url_path, "tags": item.tags});
+                if item.url_path == "1" {
+                    obj.insert(
+                        "related".into(),
+                        Value::array(related.clone().map(Value::Object)),
+                    );
+                }
+                Value::Object(obj)
+            })
+            .collect::<Vec<_>>();
+        runtime.set_global("items".into(), Value::Array(objects));
+        runtime.set_global("url_path".into(), Value::scalar("1"));
+
+        assert_eq!(
+            template.render(&runtime).unwrap(),
+            "* [seven](/7)\n* [two](/2)\n * [five](/5)\n* [seven](/7)\n* [two](/2)\n"
+        );
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            render(r#"{% latest limit=2 format="table" %}"#).unwrap(),
+            "| Title | Date | Description |\n| --- | --- | --- |\n| [one](/1) | 2023-10-20 | About one \\| more |\n| [two](/2) | 2023-10-19 | About two \\| more |\n"
+        );
+        let cards = render(r#"{% latest limit=1 format="cards" %}"#).unwrap();
+        assert!(cards.starts_with(
+            "<div class=\"columns is-multiline latest\">\n<div class=\"column is-one-third\">"
+        ));
+        assert!(cards.contains("<p class=\"title is-5\"><a href=\"/1\">one</a></p>\n<p class=\"subtitle is-6\">2023-10-20</p>\n<p>About one | more</p>"));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text: &str| render(text).err().unwrap().to_string();
+        assert_eq!(
+            error(r#"{% latest limit="many" %}"#),
+            "liquid: Invalid value 'many' for 'limit' in latest tag. Expected a number\n"
+        );
+        assert_eq!(
+            error(r#"{% latest limit=5 format="grid" %}"#),
+            "liquid: Invalid value 'grid' for 'format' in latest tag. Expected list, cards or table\n"
+        );
+        assert_eq!(
+            error(r#"{% latest limit=5 since="yesterday" %}"#),
+            "liquid: Invalid value 'yesterday' for 'since' in latest tag. Expected a date as YYYY-MM-DD\n"
+        );
+
+        let template = parser::parse("{% latest limit=5 %}", &options())
+            .map(runtime::Template::new)
+            .unwrap();
+        assert_eq!(
+            template
+                .render(&RuntimeBuilder::new().build())
+                .err()
+                .unwrap()
+                .to_string(),
+            "liquid: No value called 'items' was passed to the render function.\n"
+        );
+    }
 }
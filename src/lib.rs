@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone as _, Utc};
 use core::error::Error;
 use std::collections::HashMap;
 use std::fs;
@@ -6,21 +6,46 @@ use std::fs::File;
 use std::io::{BufRead as _, BufReader};
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::LazyLock;
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
 
 use liquid_core::{
     Display_filter, Filter, FilterReflection, ParseFilter, Result, Runtime, Value, ValueView,
 };
 
+pub mod admonitions;
+pub mod backlinks;
+pub mod banner;
 pub mod curly;
+pub mod details_tag;
+pub mod diagrams;
 pub mod drafts;
+pub mod embed_tag;
+pub mod fences;
+pub mod git;
+pub mod highlight;
+pub mod images;
+pub mod include_dir;
 pub mod include_tag;
 pub mod latest_tag;
+pub mod math;
 pub mod new;
 pub mod notifications;
+pub mod output_tag;
 pub mod recent;
+pub mod redirects;
+pub mod related;
+pub mod seo;
+pub mod shortcode_tag;
+pub mod site_files;
+pub mod sitemap;
+pub mod sites;
+pub mod tag_args;
+pub mod tags;
+pub mod toc;
 pub mod todo;
 pub mod web;
 pub mod youtube_tag;
@@ -84,10 +109,39 @@ pub fn topath(text: &str) -> String {
         ":#?" => String::from("colon-number-sign-question-mark"),
         //        "_" => "underscore".to_string(),
         "`" => "backtick".to_string(), // grave accent
-        _ => text.replace(' ', "_").to_lowercase(),
+        _ if !text.is_empty() && text.chars().all(|chr| chr == '.') => {
+            vec!["full-stop"; text.len()].join("-")
+        }
+        _ => {
+            // Characters that would break the path or the URL are replaced by their names
+            let mut parts = vec![];
+            let mut part = String::new();
+            for chr in text.chars() {
+                if UNSAFE_IN_PATH.contains(&chr) {
+                    if !part.is_empty() {
+                        parts.push(core::mem::take(&mut part));
+                    }
+                    parts.push(topath(&chr.to_string()));
+                } else {
+                    part.push(chr);
+                }
+            }
+            if !part.is_empty() {
+                parts.push(part);
+            }
+            let path = parts.join("-").replace(' ', "_").to_lowercase();
+            // tags/index.html is the list of all the tags
+            if path == "index" {
+                String::from("index-tag")
+            } else {
+                path
+            }
+        }
     }
 }
 
+const UNSAFE_IN_PATH: [char; 5] = ['/', '\\', '#', '?', '%'];
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigNavbarLink {
@@ -101,6 +155,263 @@ pub struct ConfigAtom {
     pub max: usize,
 }
 
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum RedirectFormat {
+    Netlify,
+    Nginx,
+    Apache,
+    Json,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigToc {
+    #[serde(default = "get_toc_min_level")]
+    pub min_level: u8,
+
+    #[serde(default = "get_toc_max_level")]
+    pub max_level: u8,
+
+    #[serde(default = "get_toc_title")]
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigHighlight {
+    #[serde(default = "get_highlight_theme")]
+    pub theme: String,
+
+    #[serde(default = "get_empty_string")]
+    pub dark_theme: String,
+
+    #[serde(default = "get_false")]
+    pub line_numbers: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigAdmonition {
+    pub title: String,
+
+    #[serde(default = "get_admonition_class")]
+    pub class: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigDiagrams {
+    #[serde(default)]
+    pub commands: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigBanner {
+    #[serde(default = "get_banner_width")]
+    pub width: u32,
+
+    #[serde(default = "get_banner_height")]
+    pub height: u32,
+
+    #[serde(default = "get_banner_size")]
+    pub size: i32,
+
+    #[serde(default = "get_banner_background_color")]
+    pub background_color: String,
+
+    pub background_image: Option<String>,
+
+    #[serde(default)]
+    pub embed: Vec<banner_builder::Embed>,
+
+    #[serde(default)]
+    pub lines: Vec<banner_builder::Text>,
+
+    #[serde(default = "get_run_cache")]
+    pub cache: String,
+}
+
+impl Default for ConfigBanner {
+    fn default() -> Self {
+        Self {
+            width: get_banner_width(),
+            height: get_banner_height(),
+            size: get_banner_size(),
+            background_color: get_banner_background_color(),
+            background_image: None,
+            embed: vec![],
+            lines: vec![],
+            cache: get_run_cache(),
+        }
+    }
+}
+
+// The fields of the banner that can be changed in the front matter of a page
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PageBanner {
+    pub background_color: Option<String>,
+    pub background_image: Option<String>,
+    pub size: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigSitemap {
+    // List the images of the pages in the sitemap
+    #[serde(default = "get_false")]
+    pub images: bool,
+}
+
+// Another site the pages can link to with site:name/path links. Either the root of its source
+// or the pages.json it publishes when its config has `pages_json: true`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigSite {
+    #[serde(default = "get_empty_string")]
+    pub path: String,
+
+    #[serde(default = "get_empty_string")]
+    pub manifest: String,
+}
+
+// The related pages shown on each page. The score of each page is the sum of the shared tags,
+// the same series, the same author and the similarity of the text, each multiplied by its weight.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigRelated {
+    #[serde(default = "get_related_count")]
+    pub count: usize,
+
+    #[serde(default = "get_related_title")]
+    pub title: String,
+
+    #[serde(default = "get_related_tags")]
+    pub tags: u64,
+
+    #[serde(default = "get_related_series")]
+    pub series: u64,
+
+    #[serde(default = "get_related_author")]
+    pub author: u64,
+
+    #[serde(default = "get_related_text")]
+    pub text: u64,
+}
+
+impl Default for ConfigRelated {
+    fn default() -> Self {
+        Self {
+            count: get_related_count(),
+            title: get_related_title(),
+            tags: get_related_tags(),
+            series: get_related_series(),
+            author: get_related_author(),
+            text: get_related_text(),
+        }
+    }
+}
+
+// A section of the robots.txt
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigRobots {
+    #[serde(default = "get_robots_user_agent")]
+    pub user_agent: String,
+
+    #[serde(default)]
+    pub allow: Vec<String>,
+
+    #[serde(default)]
+    pub disallow: Vec<String>,
+
+    pub crawl_delay: Option<u32>,
+}
+
+// The fields of the .well-known/security.txt, see https://www.rfc-editor.org/rfc/rfc9116
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigSecurity {
+    // E-mail addresses or URLs
+    pub contact: Vec<String>,
+
+    pub expires: DateTime<FixedOffset>,
+
+    #[serde(default = "get_empty_string")]
+    pub encryption: String,
+
+    #[serde(default = "get_empty_string")]
+    pub acknowledgments: String,
+
+    #[serde(default = "get_empty_string")]
+    pub policy: String,
+
+    #[serde(default = "get_empty_string")]
+    pub hiring: String,
+
+    #[serde(default = "get_empty_string")]
+    pub preferred_languages: String,
+}
+
+// The image the favicons and the icons of the web app manifest are created from
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigIcon {
+    pub file: String,
+
+    // Defaults to the site_name
+    #[serde(default = "get_empty_string")]
+    pub short_name: String,
+
+    #[serde(default = "get_icon_color")]
+    pub theme_color: String,
+
+    #[serde(default = "get_icon_color")]
+    pub background_color: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigImages {
+    #[serde(default = "get_image_widths")]
+    pub widths: Vec<u32>,
+
+    #[serde(default = "get_image_sizes")]
+    pub sizes: String,
+
+    #[serde(default = "get_false")]
+    pub avif: bool,
+
+    #[serde(default = "get_image_quality")]
+    pub quality: u8,
+
+    #[serde(default = "get_run_cache")]
+    pub cache: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigEmbed {
+    #[serde(default = "get_false")]
+    pub click_to_load: bool,
+
+    #[serde(default)]
+    pub providers: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigRun {
+    #[serde(default = "get_run_cache")]
+    pub cache: String,
+
+    #[serde(default)]
+    pub commands: HashMap<String, String>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigNavbar {
@@ -127,6 +438,33 @@ pub struct ConfigArchive {
 pub struct ConfigTag {
     pub description: String,
     pub title: String,
+
+    // The title, description, aliases and parent of individual tags
+    #[serde(default)]
+    pub details: Vec<TagDetails>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TagDetails {
+    pub name: String,
+
+    #[serde(default = "get_empty_string")]
+    pub title: String,
+
+    #[serde(default = "get_empty_string")]
+    pub description: String,
+
+    // Other spellings of the tag used in the front matter, e.g. rustlang for rust
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
+    #[serde(default = "get_empty_string")]
+    pub parent: String,
+
+    // The HTML of tags/<tag>.md shown on the page of the tag
+    #[serde(default = "get_empty_string")]
+    pub text: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -138,10 +476,15 @@ pub struct Author {
 
     #[serde(default = "get_empty_string")]
     pub text: String,
+
+    // The handle on X/Twitter, with or without the @
+    #[serde(default = "get_empty_string")]
+    pub twitter: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
+#[expect(clippy::struct_excessive_bools)]
 pub struct Config {
     pub url: String,
     pub repo: String,
@@ -167,13 +510,97 @@ pub struct Config {
     #[serde(default = "get_empty_string")]
     pub google_analytics: String,
 
+    // The handle of the site on X/Twitter, with or without the @
+    #[serde(default = "get_empty_string")]
+    pub twitter: String,
+
+    // The URL of a search with {search_term_string} in place of the query, added to the structured data
+    #[serde(default = "get_empty_string")]
+    pub search: String,
+
     #[serde(default = "get_false")]
     pub show_related: bool,
 
     #[serde(default = "get_empty_string")]
     pub related_pages_title: String,
 
+    pub related: Option<ConfigRelated>,
+
     pub atom: Option<ConfigAtom>,
+
+    #[serde(default = "get_empty_string")]
+    pub timezone: String,
+
+    #[serde(default = "get_false")]
+    pub git_metadata: bool,
+
+    #[serde(default = "get_empty_string")]
+    pub permalink: String,
+
+    #[serde(default)]
+    pub redirects: Vec<RedirectFormat>,
+
+    pub toc: Option<ConfigToc>,
+
+    pub highlight: Option<ConfigHighlight>,
+
+    #[serde(default = "get_include_size_limit")]
+    pub include_size_limit: u64,
+
+    #[serde(default = "get_include_exclude")]
+    pub include_exclude: Vec<String>,
+
+    pub run: Option<ConfigRun>,
+
+    pub embed: Option<ConfigEmbed>,
+
+    #[serde(default)]
+    pub admonitions: HashMap<String, ConfigAdmonition>,
+
+    #[serde(default = "get_false")]
+    pub math: bool,
+
+    pub diagrams: Option<ConfigDiagrams>,
+
+    pub images: Option<ConfigImages>,
+
+    pub banner: Option<ConfigBanner>,
+
+    pub sitemap: Option<ConfigSitemap>,
+
+    #[serde(default)]
+    pub robots: Vec<ConfigRobots>,
+
+    #[serde(default = "get_false")]
+    pub humans: bool,
+
+    pub security: Option<ConfigSecurity>,
+
+    pub icon: Option<ConfigIcon>,
+
+    #[serde(default)]
+    pub sites: HashMap<String, ConfigSite>,
+
+    // Publish pages.json with the pages of the site for the sites linking to it
+    #[serde(default = "get_false")]
+    pub pages_json: bool,
+}
+
+impl Config {
+    // The offset used for timestamps that don't have one. Validated in `read_config_file`.
+    pub fn offset(&self) -> FixedOffset {
+        parse_timezone(&self.timezone).unwrap_or_else(|_| get_utc())
+    }
+
+    // The name of the tag an alias stands for, or the tag itself
+    pub fn canonical_tag(&self, tag: &str) -> String {
+        let lower = tag.to_lowercase();
+        self.tags
+            .details
+            .iter()
+            .find(|details| details.aliases.contains(&lower))
+            .map_or_else(|| tag.to_owned(), |details| details.name.clone())
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -195,13 +622,48 @@ pub struct Mp3 {
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
+#[expect(clippy::struct_excessive_bools)]
 pub struct Page {
     pub title: String,
     pub timestamp: String,
 
+    #[serde(default = "get_empty_string")]
+    pub updated: String,
+
+    // Parsed from the timestamp and the updated fields by read_md_file
+    #[serde(skip_deserializing)]
+    pub date: DateTime<FixedOffset>,
+
+    #[serde(skip_deserializing)]
+    pub modified: DateTime<FixedOffset>,
+
+    // Filled from the git history of the file if `git_metadata` is enabled in the config
+    #[serde(skip_deserializing)]
+    pub created: Option<DateTime<FixedOffset>>,
+
+    #[serde(skip_deserializing)]
+    pub last_modified: Option<DateTime<FixedOffset>>,
+
+    #[serde(skip_deserializing)]
+    pub contributors: Vec<String>,
+
+    // The path of the file in the git repository, for the edit and history links
+    #[serde(skip_deserializing)]
+    pub source_path: String,
+
     #[serde(default = "get_empty_string")]
     pub url_path: String,
 
+    #[serde(default = "get_empty_string")]
+    pub slug: String,
+
+    #[serde(default = "get_empty_string")]
+    pub permalink: String,
+
+    // The previous URLs of the page, they are redirected to the current one
+    #[serde(default = "get_empty_vector")]
+    pub aliases: Vec<String>,
+
     #[serde(default = "get_empty_string")]
     pub filename: String,
 
@@ -217,12 +679,41 @@ pub struct Page {
     #[serde(default = "get_empty_string")]
     pub content: String,
 
+    // The line number of the first line of the content in the file, used in the error messages
+    #[serde(skip_deserializing)]
+    pub content_line: usize,
+
+    // The `toc` field of the front-matter, false turns off the table of contents of the page
+    #[serde(default = "get_true", rename(deserialize = "toc"))]
+    pub show_toc: bool,
+
+    // The table of contents, filled by markdown_pages if `toc` is enabled in the config
+    #[serde(skip_deserializing)]
+    pub toc: Vec<toc::Heading>,
+
+    // The languages of the diagrams drawn in the browser, filled by markdown_pages
+    #[serde(skip_deserializing)]
+    pub diagrams: Vec<String>,
+
     #[serde(default = "get_empty_links")]
     pub backlinks: Vec<Link>,
 
+    // The most related pages, filled by related::collect_related if `related` is set in the config
+    #[serde(skip_deserializing)]
+    pub related: Vec<related::Related>,
+
+    // The name of the series of articles the page belongs to
+    #[serde(default = "get_empty_string")]
+    pub series: String,
+
     #[serde(default = "get_empty_string")]
     pub author: String,
 
+    // The Open Graph image of the page instead of the generated banner, a URL or a path in the site
+    pub image: Option<String>,
+
+    pub banner: Option<PageBanner>,
+
     pub redirect: Option<String>,
 
     pub published: bool,
@@ -230,11 +721,18 @@ pub struct Page {
     #[serde(default = "get_true")]
     pub show_related: bool,
 
+    // Ask the search engines not to index the page. These pages are also left out of the sitemap.
+    #[serde(default = "get_false")]
+    pub noindex: bool,
+
+    #[serde(default = "get_true")]
+    pub sitemap: bool,
+
     // TODO: add a list of books
     #[serde(default = "get_empty_vector")]
     pub books: Vec<String>,
 
-    // TODO: need to make use of this field for cross-site linking
+    // The canonical URL of a page first published elsewhere, either a URL or a site:name/path link
     #[serde(default = "get_empty_string")]
     pub original: String,
 
@@ -260,17 +758,37 @@ impl Page {
         Page {
             title: String::new(),
             timestamp: String::new(),
+            updated: String::new(),
+            date: DateTime::default(),
+            modified: DateTime::default(),
+            created: None,
+            last_modified: None,
+            contributors: vec![],
+            source_path: String::new(),
             description: String::new(),
             url_path: String::new(),
+            slug: String::new(),
+            permalink: String::new(),
+            aliases: vec![],
             filename: String::new(),
             content: String::new(),
+            content_line: 1,
+            show_toc: true,
+            toc: vec![],
+            diagrams: vec![],
             todo: vec![],
             tags: vec![],
             backlinks: vec![],
+            related: vec![],
+            series: String::new(),
             published: false,
             redirect: None,
             show_related: true,
+            noindex: false,
+            sitemap: true,
             author: String::new(),
+            image: None,
+            banner: None,
             books: vec![],
             original: String::new(),
             translator: String::new(),
@@ -287,6 +805,20 @@ impl Default for Page {
     }
 }
 
+#[cfg(test)]
+impl Page {
+    // A published page for the tests, titled by its path. The other fields are set by the tests.
+    pub fn for_test(url_path: &str, tags: &[&str]) -> Page {
+        Page {
+            title: url_path.to_owned(),
+            url_path: url_path.to_owned(),
+            tags: tags.iter().map(|tag| (*tag).to_owned()).collect(),
+            published: true,
+            ..Page::default()
+        }
+    }
+}
+
 fn get_true() -> bool {
     true
 }
@@ -307,6 +839,102 @@ fn get_empty_string() -> String {
     String::new()
 }
 
+fn get_toc_min_level() -> u8 {
+    2
+}
+
+fn get_toc_max_level() -> u8 {
+    3
+}
+
+fn get_toc_title() -> String {
+    String::from("Table of Contents")
+}
+
+fn get_highlight_theme() -> String {
+    String::from("InspiredGitHub")
+}
+
+fn get_admonition_class() -> String {
+    String::from("is-dark")
+}
+
+fn get_run_cache() -> String {
+    String::from(".cache")
+}
+
+fn get_banner_width() -> u32 {
+    1000
+}
+
+fn get_banner_height() -> u32 {
+    500
+}
+
+fn get_banner_size() -> i32 {
+    24
+}
+
+fn get_banner_background_color() -> String {
+    String::from("FFFFFF")
+}
+
+fn get_image_widths() -> Vec<u32> {
+    vec![480, 960, 1440]
+}
+
+fn get_image_sizes() -> String {
+    String::from("100vw")
+}
+
+fn get_image_quality() -> u8 {
+    70
+}
+
+fn get_related_count() -> usize {
+    5
+}
+
+fn get_related_title() -> String {
+    String::from("Related Articles")
+}
+
+fn get_related_tags() -> u64 {
+    3
+}
+
+fn get_related_series() -> u64 {
+    5
+}
+
+fn get_related_author() -> u64 {
+    1
+}
+
+fn get_related_text() -> u64 {
+    2
+}
+
+fn get_robots_user_agent() -> String {
+    String::from("*")
+}
+
+fn get_icon_color() -> String {
+    String::from("#ffffff")
+}
+
+fn get_include_exclude() -> Vec<String> {
+    vec![String::from(".git/"), String::from("target/")]
+}
+
+fn get_include_size_limit() -> u64 {
+    100_000
+}
+
+fn get_utc() -> FixedOffset {
+    FixedOffset::east_opt(0).unwrap()
+}
+
 fn get_empty_mp3() -> Mp3 {
     Mp3 {
         file: String::new(),
@@ -315,83 +943,247 @@ fn get_empty_mp3() -> Mp3 {
     }
 }
 
-pub fn markdown_pages(pages: Vec<Page>) -> Vec<Page> {
+pub fn markdown_pages(config: &Config, pages: Vec<Page>) -> Result<Vec<Page>, String> {
+    let mut admonitions = admonitions::default_admonitions();
+    admonitions.extend(config.admonitions.clone());
+
     pages
         .into_iter()
         .map(|mut page| {
-            page.content = markdown2html(&page.content)
-                .replace("<h1>", "<h1 class=\"title\">")
-                .replace("<h2>", "<h2 class=\"title is-4\">")
-                .replace("<h3>", "<h3 class=\"title is-5\">");
-            page
+            page.content =
+                admonitions::convert_admonitions(&page.content, &admonitions, config.math)
+                    .map_err(|err| format!("{err} in '{}'", page.filename))?;
+            (page.content, page.diagrams) =
+                diagrams::render_diagrams(&page.content, config.diagrams.as_ref(), config.math)
+                    .map_err(|err| format!("{err} in '{}'", page.filename))?;
+            if let Some(highlight) = &config.highlight {
+                page.content =
+                    highlight::highlight_code_blocks(&page.content, highlight, config.math)
+                        .map_err(|err| format!("{err} in '{}'", page.filename))?;
+            }
+            let toc = if page.show_toc {
+                config.toc.as_ref()
+            } else {
+                None
+            };
+            let mut html = markdown2html(&page.content, config.math);
+            if config.math {
+                html = math::render_math(&html)
+                    .map_err(|err| format!("{err} in '{}'", page.filename))?;
+            }
+            let (content, headings) = toc::add_heading_ids(&html, toc);
+            page.content = content;
+            page.toc = headings;
+            Ok(page)
         })
         .collect()
 }
 
-pub fn get_files_to_copy(pages: &Vec<Page>) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+// The files we generate. The links to them don't need to be copied.
+const GENERATED_FILES: [&str; 8] = [
+    "atom.xml",
+    "sitemap.xml",
+    "robots.txt",
+    "humans.txt",
+    ".well-known/security.txt",
+    "manifest.webmanifest",
+    "favicon.ico",
+    "pages.json",
+];
+
+// The files linked from the pages that need to be copied to the site. The images and the linked files
+// (e.g. [slides](files/talk.pdf)) are looked up relative to the root or in the static/ directory.
+pub fn get_files_to_copy(
+    config: &Config,
+    root: &str,
+    pages: &Vec<Page>,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
     let mut paths_to_copy: Vec<PathBuf> = vec![];
-    let re = Regex::new(r"!\[[^\]]*\]\(([^)]+)\)").unwrap();
-    let ext_images: Vec<&str> = vec!["png", "jpg", "jpeg", "gif", "webp"];
+    let zips = pages
+        .iter()
+        .flat_map(|page| generated_zips(&page.content, config.math))
+        .collect::<Vec<String>>();
 
     for page in pages {
-        let mut in_code = false;
-        for row in page.content.split('\n') {
-            if row.starts_with("```") {
-                in_code = !in_code;
-                continue;
-            }
-            if !in_code {
-                if let Some(value) = re.captures(row) {
-                    let path = Path::new(&value[1]);
-                    // TODO: we don't need to copy external images
-                    let extension = &path
-                        .extension()
-                        .ok_or(format!("No extension in '{:?}'", path.display()))?
-                        .to_str()
-                        .ok_or("Could not convert to str '{path:?}'")?;
-                    if ext_images.contains(extension) {
-                        paths_to_copy.push(path.to_path_buf().clone());
-                    } else {
+        if let Some(image) = &page.image {
+            if !is_external(image) {
+                let path = PathBuf::from(image.trim_start_matches('/'));
+                if !Path::new(root).join("static").join(&path).is_file() {
+                    if !Path::new(root).join(&path).is_file() {
                         let err = format!(
-                            "Unhandled extension '{}' for file '{:?}' to be copied in page {}",
-                            extension,
-                            path.file_name().unwrap().display(),
+                            "The image '{}' in the front matter of '{}' does not exist",
+                            path.display(),
                             page.filename
                         );
                         return Err(err.into());
                     }
+                    if !paths_to_copy.contains(&path) {
+                        paths_to_copy.push(path);
+                    }
                 }
             }
         }
-        if in_code {
+
+        if fences::code_blocks(&page.content, config.math)
+            .iter()
+            .any(|block| !block.closed)
+        {
             let err = format!("Still in_code after ending file '{:?}'", page.filename);
             return Err(err.into());
         }
+        let fenced = fences::fenced_lines(&page.content, config.math);
+        for (index, row) in page.content.split('\n').enumerate() {
+            if fenced[index] {
+                continue;
+            }
+            let line = page.content_line + index;
+            let text = curly::protect_inline_code(row, &mut vec![]);
+            for path in videos_to_copy(root, &text)
+                .map_err(|err| format!("{err} in '{}' line {line}", page.filename))?
+            {
+                if !paths_to_copy.contains(&path) {
+                    paths_to_copy.push(path);
+                }
+            }
+        }
+
+        for target in fences::targets(&page.content, config.math) {
+            let line = page.content_line + target.line;
+            let path = file_to_copy(root, &target, &zips, &page.filename, line)?;
+            if let Some(path) = path {
+                if !paths_to_copy.contains(&path) {
+                    paths_to_copy.push(path);
+                }
+            }
+        }
     }
 
     Ok(paths_to_copy)
 }
 
-fn collect_backlinks(pages: Vec<Page>) -> Vec<Page> {
-    let links = collect_all_the_internal_links(&pages);
+// The local file of a link or an image that is not generated and not in the static directory
+fn file_to_copy(
+    root: &str,
+    target: &fences::Target,
+    zips: &[String],
+    filename: &str,
+    line: usize,
+) -> Result<Option<PathBuf>, String> {
+    let ext_images = ["png", "jpg", "jpeg", "gif", "webp", "svg", "avif"];
+    let ext_pages = ["md", "html", "htm"];
+
+    let url = target.url.split(['#', '?']).next().unwrap_or_default();
+    if url.is_empty() || is_external(url) {
+        return Ok(None);
+    }
+    let path = PathBuf::from(url.trim_start_matches('/'));
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+
+    if target.image {
+        let extension = extension.ok_or_else(|| {
+            format!(
+                "No extension in '{}' in '{filename}' line {line}",
+                path.display()
+            )
+        })?;
+        if !ext_images.contains(&extension.as_str()) {
+            return Err(format!(
+                "Unhandled extension '{extension}' for file '{:?}' to be copied in page {filename} line {line}",
+                path.file_name().unwrap_or_default().display(),
+            ));
+        }
+    } else {
+        // Links to pages
+        let Some(extension) = extension else {
+            return Ok(None);
+        };
+        let name = path.to_string_lossy();
+        if ext_pages.contains(&extension.as_str())
+            || GENERATED_FILES.contains(&name.as_ref())
+            || zips.iter().any(|zip| *zip == name)
+        {
+            return Ok(None);
+        }
+    }
+
+    if Path::new(root).join("static").join(&path).is_file() {
+        return Ok(None);
+    }
+    if !Path::new(root).join(&path).is_file() {
+        return Err(format!(
+            "The file '{}' referenced in '{filename}' line {line} does not exist",
+            path.display()
+        ));
+    }
+    Ok(Some(path))
+}
 
-    pages
-        .into_iter()
-        .map(|mut page| {
-            // TODO can we limit the clone to the already filtered values?
-            let mut backlinks = links
-                .clone()
-                .into_iter()
-                .filter(|link| link.to_path == format!("/{}", page.url_path))
-                .collect::<Vec<Link>>();
-            backlinks.sort_by(|a, b| b.from_title.cmp(&a.from_title));
-            page.backlinks = backlinks;
-            //log::info!("{:?}", page.backlinks);
-            page
+// The zip files created by {% include dir="examples/project" zip=true %}
+fn generated_zips(content: &str, math: bool) -> Vec<String> {
+    static INCLUDE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\{%-?\s*include\s([^%]*)%\}").unwrap());
+    static DIR: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"\bdir\s*=\s*["']([^"']+)["']"#).unwrap());
+    static ZIP: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r#"\bzip\b(?:\s*=\s*["']?(\w+))?"#).unwrap());
+
+    let fenced = fences::fenced_lines(content, math);
+    content
+        .split('\n')
+        .zip(fenced)
+        .filter(|(_row, fenced)| !fenced)
+        .flat_map(|(row, _fenced)| INCLUDE.captures_iter(row).collect::<Vec<_>>())
+        .filter(|tag| {
+            ZIP.captures(&tag[1])
+                .is_some_and(|zip| zip.get(1).is_none_or(|value| value.as_str() == "true"))
+        })
+        .filter_map(|tag| {
+            DIR.captures(&tag[1])
+                .map(|dir| include_dir::zip_path(&dir[1]))
         })
         .collect()
 }
 
+// The local copies of the videos in a row that are not in the static directory:
+// {% youtube id="..." file="videos/intro.mp4" %}
+fn videos_to_copy(root: &str, row: &str) -> Result<Vec<PathBuf>, String> {
+    static VIDEO: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"\{%-?\s*youtube\s[^%]*\bfile\s*=\s*["']([^"']+)["']"#).unwrap()
+    });
+
+    let mut paths = vec![];
+    for value in VIDEO.captures_iter(row) {
+        if is_external(&value[1]) {
+            continue;
+        }
+        let path = PathBuf::from(value[1].trim_start_matches('/'));
+        if Path::new(root).join("static").join(&path).is_file() {
+            continue;
+        }
+        if !Path::new(root).join(&path).is_file() {
+            return Err(format!(
+                "The video '{}' of the youtube tag does not exist",
+                path.display()
+            ));
+        }
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+// https://..., //cdn..., mailto:...
+fn is_external(target: &str) -> bool {
+    target.starts_with("//")
+        || target.split_once(':').is_some_and(|(scheme, _rest)| {
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|chr| chr.is_ascii_alphanumeric() || "+-.".contains(chr))
+        })
+}
+
 pub fn read_pages(config: &Config, path: &Path, root: &str) -> Vec<Page> {
     log::info!("read_page from path '{}'", path.display());
     let mut pages: Vec<Page> = vec![];
@@ -423,24 +1215,40 @@ pub fn read_pages(config: &Config, path: &Path, root: &str) -> Vec<Page> {
         pages.push(page);
     }
 
-    match check_unique_dates(&pages) {
+    if config.git_metadata {
+        pages = git::add_history(pages, path, root);
+    }
+
+    match check_unique_urls(&pages) {
         Ok(()) => {}
         Err(err) => {
             log::error!("{err}");
             std::process::exit(1);
         }
     }
-    pages.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+    pages.sort_by(|a, b| {
+        b.date
+            .cmp(&a.date)
+            .then_with(|| a.filename.cmp(&b.filename))
+    });
+
+    let (date, modified) = if pages.is_empty() {
+        let now = Utc::now().fixed_offset();
+        (now, now)
+    } else {
+        (
+            pages[0].date,
+            pages.iter().map(|page| page.modified).max().unwrap(),
+        )
+    };
 
     let archive = Page {
         url_path: String::from("archive"),
         published: true,
-        timestamp: if pages.is_empty() {
-            let now: DateTime<Utc> = Utc::now();
-            now.format("%Y-%m-%dT%H:%M:%S").to_string()
-        } else {
-            pages[0].timestamp.clone()
-        },
+        timestamp: date.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        date,
+        modified,
         ..Page::default()
     };
 
@@ -449,19 +1257,46 @@ pub fn read_pages(config: &Config, path: &Path, root: &str) -> Vec<Page> {
     pages
 }
 
-fn check_unique_dates(pages: &Vec<Page>) -> Result<(), String> {
-    let mut uniq = std::collections::HashSet::new();
+fn check_unique_urls(pages: &Vec<Page>) -> Result<(), String> {
+    let mut uniq = HashMap::new();
     for page in pages {
-        if uniq.contains(&page.timestamp) {
-            return Err(format!("duplicate timestamp '{}'", page.timestamp));
+        if let Some(filename) = uniq.insert(page.url_path.clone(), page.filename.clone()) {
+            return Err(format!(
+                "Both '{}' and '{}' have the URL '/{}'",
+                filename, page.filename, page.url_path
+            ));
         }
-        uniq.insert(page.timestamp.clone());
     }
 
     Ok(())
 }
 
-pub fn read_md_file(_config: &Config, _root: &str, path: &str) -> Result<Page, String> {
+// Accept either an RFC 3339 timestamp with an offset (2015-10-11T12:30:01+02:00)
+// or a naive one (2015-10-11T12:30:01) that is taken to be in the given offset.
+pub fn parse_timestamp(
+    text: &str,
+    offset: &FixedOffset,
+) -> Result<DateTime<FixedOffset>, chrono::ParseError> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Ok(date);
+    }
+    let naive = NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S")?;
+
+    // A fixed offset has no gaps or folds so there is always exactly one result
+    Ok(offset.from_local_datetime(&naive).unwrap())
+}
+
+// The timezone in the config is either empty, "UTC", "Z" or an offset like "+02:00".
+fn parse_timezone(timezone: &str) -> Result<FixedOffset, String> {
+    if timezone.is_empty() || timezone == "UTC" || timezone == "Z" {
+        return Ok(get_utc());
+    }
+    timezone
+        .parse::<FixedOffset>()
+        .map_err(|err| format!("Invalid timezone '{timezone}': {err}"))
+}
+
+pub fn read_md_file(config: &Config, _root: &str, path: &str) -> Result<Page, String> {
     let mut page = Page::new();
     log::debug!("read_md_file '{path}'");
 
@@ -476,12 +1311,14 @@ pub fn read_md_file(_config: &Config, _root: &str, path: &str) -> Result<Page, S
             let reader = BufReader::new(file);
             let mut in_front_matter = false;
             let mut front_matter = String::new();
-            for line in reader.lines() {
+            let mut content_line = 1;
+            for (index, line) in reader.lines().enumerate() {
                 let line = line.unwrap();
                 // log::debug!("line '{}'", line);
                 if in_front_matter {
                     if line == "---" {
                         in_front_matter = false;
+                        content_line = index + 2;
                         // log::info!("front_matter: '{}'", &front_matter);
                         match serde_yaml::from_str(&front_matter) {
                             Ok(val) => page = val,
@@ -504,6 +1341,7 @@ pub fn read_md_file(_config: &Config, _root: &str, path: &str) -> Result<Page, S
                 content += &line;
                 content += "\n";
             }
+            page.content_line = content_line;
         }
         Err(error) => {
             log::error!("Error opening file {path}: {error}");
@@ -513,80 +1351,119 @@ pub fn read_md_file(_config: &Config, _root: &str, path: &str) -> Result<Page, S
     if page.tags.iter().any(String::is_empty) {
         return Err(format!("There is an empty tag in {path}"));
     }
+    let mut tags: Vec<String> = vec![];
+    for tag in page.tags.iter().map(|tag| config.canonical_tag(tag)) {
+        if !tags
+            .iter()
+            .any(|seen| seen.to_lowercase() == tag.to_lowercase())
+        {
+            tags.push(tag);
+        }
+    }
+    page.tags = tags;
 
     let mut p = PathBuf::from(path);
     page.filename = p.file_name().unwrap().to_str().unwrap().to_string();
     p.set_extension("");
-    page.url_path = p.file_name().unwrap().to_str().unwrap().to_string();
-
-    if page.url_path == "index" {
-        page.url_path = String::new();
-    }
+    let stem = p.file_name().unwrap().to_str().unwrap().to_string();
 
     page.content = content;
 
     if page.title.is_empty() {
         return Err(format!("Missing title in '{path}'"));
     }
-    match chrono::NaiveDateTime::parse_from_str(&page.timestamp, "%Y-%m-%dT%H:%M:%S") {
-        Ok(_) => {
-            let _x = 1;
-        }
+    page.date = match parse_timestamp(&page.timestamp, &config.offset()) {
+        Ok(date) => date,
         Err(err) => {
             return Err(format!(
                 "Invalid date '{}' in {}: {}",
                 page.timestamp, path, err
             ));
         }
-    }
+    };
+
+    page.modified = if page.updated.is_empty() {
+        page.date
+    } else {
+        match parse_timestamp(&page.updated, &config.offset()) {
+            Ok(date) => date,
+            Err(err) => {
+                return Err(format!(
+                    "Invalid updated date '{}' in {}: {}",
+                    page.updated, path, err
+                ));
+            }
+        }
+    };
+
+    page.url_path = get_url_path(config, &page, &stem).map_err(|err| format!("{err} in {path}"))?;
 
     Ok(page)
 }
 
-fn markdown2html(content: &str) -> String {
+// The URL of a page is the name of the file unless the page or the config has a permalink pattern.
+// A pattern ending with / means the page is saved as index.html in a directory of that name.
+fn get_url_path(config: &Config, page: &Page, stem: &str) -> Result<String, String> {
+    if stem == "index" {
+        return Ok(String::new());
+    }
+
+    let slug = if page.slug.is_empty() {
+        stem
+    } else {
+        &page.slug
+    };
+
+    let pattern = if !page.permalink.is_empty() {
+        &page.permalink
+    } else if !config.permalink.is_empty() {
+        &config.permalink
+    } else {
+        return Ok(slug.to_owned());
+    };
+
+    let url_path = expand_permalink(pattern, &page.date, slug)?;
+    if url_path.is_empty() || url_path.contains("..") {
+        return Err(format!("Invalid permalink '{pattern}'"));
+    }
+
+    Ok(url_path)
+}
+
+fn expand_permalink(
+    pattern: &str,
+    date: &DateTime<FixedOffset>,
+    slug: &str,
+) -> Result<String, String> {
+    let url_path = pattern
+        .replace(":year", &date.format("%Y").to_string())
+        .replace(":month", &date.format("%m").to_string())
+        .replace(":day", &date.format("%d").to_string())
+        .replace(":slug", slug);
+
+    if url_path.contains(':') {
+        return Err(format!("Unknown placeholder in permalink '{pattern}'"));
+    }
+
+    Ok(url_path.trim_start_matches('/').to_owned())
+}
+
+fn markdown2html(content: &str, math: bool) -> String {
     markdown::to_html_with_options(
         content,
         &markdown::Options {
+            parse: fences::parse_options(math),
             compile: markdown::CompileOptions {
                 allow_dangerous_html: true,
                 //allow_dangerous_protocol: true,
                 ..markdown::CompileOptions::default()
             },
-            ..markdown::Options::gfm()
         },
     )
     .unwrap()
 }
 
-fn collect_all_the_internal_links(pages: &[Page]) -> Vec<Link> {
-    pages
-        .iter()
-        .map(find_links)
-        .collect::<Vec<Vec<Link>>>()
-        .concat()
-}
-
-fn find_links(page: &Page) -> Vec<Link> {
-    let mut links: Vec<Link> = vec![];
-
-    // TODO include the internal links that have the site URL as well.
-    let re = Regex::new(r#"\[([^]]+)\]\(([^)]+)\)"#).unwrap();
-    for capture in re.captures_iter(&page.content) {
-        if capture[2].starts_with("http://") || capture[2].starts_with("https://") {
-            continue;
-        }
-        links.push(Link {
-            from_title: page.title.clone(),
-            from_path: page.url_path.clone(),
-            to_title: capture[1].to_string(),
-            to_path: capture[2].to_string(),
-        });
-    }
-
-    links
-}
-
-fn copy_files(root: &str, outdir: &str, paths: &Vec<PathBuf>) {
+fn copy_files(root: &str, outdir: &str, paths: &Vec<PathBuf>) -> Result<(), String> {
     for path in paths {
         let include_path = Path::new(root).join(path);
         let output_path = Path::new(outdir).join(path);
@@ -595,11 +1472,12 @@ fn copy_files(root: &str, outdir: &str, paths: &Vec<PathBuf>) {
             include_path.display(),
             output_path.display()
         );
-        copy_file(&include_path, &output_path);
+        copy_file(&include_path, &output_path)?;
     }
+    Ok(())
 }
 
-fn copy_file(source_path: &Path, destination_path: &PathBuf) {
+fn copy_file(source_path: &Path, destination_path: &PathBuf) -> Result<(), String> {
     log::info!(
         "copy_path: from {} to {}",
         source_path.display(),
@@ -608,14 +1486,60 @@ fn copy_file(source_path: &Path, destination_path: &PathBuf) {
     let destination_dir = destination_path.parent().unwrap();
     log::info!("dir: {}", destination_dir.display());
     if !source_path.exists() {
-        log::error!("source_path: {} does not exists", source_path.display());
-        return;
+        return Err(format!(
+            "The file '{}' does not exist",
+            source_path.display()
+        ));
     }
 
     if !destination_dir.exists() {
-        fs::create_dir_all(destination_dir).unwrap();
+        fs::create_dir_all(destination_dir).map_err(|err| err.to_string())?;
+    }
+    fs::copy(source_path, destination_path).map_err(|err| {
+        format!(
+            "Could not copy '{}' to '{}': {err}",
+            source_path.display(),
+            destination_path.display()
+        )
+    })?;
+    Ok(())
+}
+
+// Copy all the files of a directory, e.g. the static/ and assets/ directories of the site
+fn copy_dir(source_dir: &Path, destination_dir: &Path) -> Result<(), String> {
+    let entries = fs::read_dir(source_dir)
+        .map_err(|err| format!("Could not read directory '{}': {err}", source_dir.display()))?;
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        let destination = destination_dir.join(path.file_name().unwrap_or_default());
+        if path.is_dir() {
+            copy_dir(&path, &destination)?;
+        } else {
+            copy_file(&path, &destination)?;
+        }
+    }
+    Ok(())
+}
+
+// Escape text for HTML, both for the content of elements and for the values of attributes
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// The name of a cache entry made from everything that goes into it. Unlike the hashers of std
+// it is the same on every platform and in every version so the caches can be kept between builds.
+pub fn stable_hash(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        // The length keeps ["ab", "c"] and ["a", "bc"] apart
+        hasher.update(format!("{}:", part.len()));
+        hasher.update(part);
     }
-    fs::copy(source_path, destination_path).unwrap();
+    format!("{:x}", hasher.finalize())
 }
 
 fn read_languages() -> HashMap<String, String> {
@@ -682,6 +1606,38 @@ pub fn read_config_file(filepath: PathBuf, authors: &str) -> Result<Config, Stri
         uniq.insert(nickname);
     }
 
+    parse_timezone(&config.timezone)?;
+    if let Some(toc) = &config.toc {
+        if toc.min_level < 1 || toc.min_level > toc.max_level || toc.max_level > 6 {
+            return Err(format!(
+                "Invalid toc levels {}-{} in config.yaml",
+                toc.min_level, toc.max_level
+            ));
+        }
+    }
+    expand_permalink(&config.permalink, &DateTime::default(), "")?;
+    if config
+        .security
+        .as_ref()
+        .is_some_and(|security| security.contact.is_empty())
+    {
+        return Err(String::from(
+            "The security section of config.yaml needs at least one contact",
+        ));
+    }
+    let mut names = config.sites.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        let site = &config.sites[name];
+        if site.path.is_empty() == site.manifest.is_empty() {
+            return Err(format!(
+                "The site '{name}' in config.yaml needs either a path or a manifest"
+            ));
+        }
+    }
+    config.tags.details = read_tag_details(config.tags.details, authors)?;
+    config.twitter = config.twitter.trim_start_matches('@').into();
+
     config.authors = config
         .authors
         .into_iter()
@@ -690,7 +1646,8 @@ pub fn read_config_file(filepath: PathBuf, authors: &str) -> Result<Config, Stri
                 .join("authors")
                 .join(format!("{}.md", author.nickname));
             let content = std::fs::read_to_string(author_file).unwrap_or_default();
-            author.text = markdown2html(&content);
+            author.text = markdown2html(&content, false);
+            author.twitter = author.twitter.trim_start_matches('@').into();
             author
         })
         .collect::<Vec<Author>>();
@@ -698,6 +1655,61 @@ pub fn read_config_file(filepath: PathBuf, authors: &str) -> Result<Config, Stri
     Ok(config)
 }
 
+// Lowercase the tags in the details, check them and read the text of each tag from tags/<tag>.md
+fn read_tag_details(details: Vec<TagDetails>, root: &str) -> Result<Vec<TagDetails>, String> {
+    let details = details
+        .into_iter()
+        .map(|mut details| {
+            details.name = details.name.to_lowercase();
+            details.parent = details.parent.to_lowercase();
+            details.aliases = details
+                .aliases
+                .iter()
+                .map(|alias| alias.to_lowercase())
+                .collect();
+            let tag_file = Path::new(root)
+                .join("tags")
+                .join(format!("{}.md", topath(&details.name)));
+            let content = std::fs::read_to_string(tag_file).unwrap_or_default();
+            details.text = markdown2html(&content, false);
+            details
+        })
+        .collect::<Vec<_>>();
+
+    let mut seen = std::collections::HashSet::new();
+    for name in details
+        .iter()
+        .flat_map(|details| core::iter::once(&details.name).chain(&details.aliases))
+    {
+        if !seen.insert(name) {
+            return Err(format!("The tag '{name}' appears twice in config.yaml"));
+        }
+    }
+
+    for tag in &details {
+        let mut current = tag;
+        let mut depth = 0;
+        while !current.parent.is_empty() {
+            let Some(parent) = details.iter().find(|other| other.name == current.parent) else {
+                return Err(format!(
+                    "The parent '{}' of the tag '{}' is not in the tags of config.yaml",
+                    current.parent, current.name
+                ));
+            };
+            depth += 1;
+            if depth > details.len() {
+                return Err(format!(
+                    "The parents of the tag '{}' form a loop in config.yaml",
+                    tag.name
+                ));
+            }
+            current = parent;
+        }
+    }
+
+    Ok(details)
+}
+
 pub fn filter_words(words: &[String]) -> Vec<String> {
     words
         .to_owned()
@@ -713,7 +1725,16 @@ mod tests {
 
     #[test]
     pub fn test_topath() {
-        let cases = vec![("hello", "hello"), ("#", "number-sign")];
+        let cases = vec![
+            ("hello", "hello"),
+            ("#", "number-sign"),
+            ("With Space", "with_space"),
+            ("..", "full-stop-full-stop"),
+            ("c#", "c-number-sign"),
+            ("tcp/ip", "tcp-forward-slash-ip"),
+            ("50%", "50-percent-sign"),
+            ("index", "index-tag"),
+        ];
 
         for entry in cases {
             let text = "{{ text | topath}}";
@@ -740,8 +1761,11 @@ fn test_read_index() {
     let expected_page = Page {
         title: "Index page".to_string(),
         timestamp: "2015-10-11T12:30:01".to_string(),
+        date: DateTime::parse_from_rfc3339("2015-10-11T12:30:01Z").unwrap(),
+        modified: DateTime::parse_from_rfc3339("2015-10-11T12:30:01Z").unwrap(),
         description: "The text for the search engines".to_string(),
         filename: "index.md".to_string(),
+        content_line: 7,
         content: "\nSome Text.\n\nSome more text after an empty row.\n\n## A title with two hash-marks\n\nMore text [with TODO](/with_todo).\n".to_string(),
         published: true,
         archive: true,
@@ -764,8 +1788,11 @@ fn test_read_todo() {
     let expected_page = Page {
         title: "Page with todos".to_string(),
         timestamp: "2023-10-11T12:30:01".to_string(),
+        date: DateTime::parse_from_rfc3339("2023-10-11T12:30:01Z").unwrap(),
+        modified: DateTime::parse_from_rfc3339("2023-10-11T12:30:01Z").unwrap(),
         url_path: "with_todo".to_string(),
         filename: "with_todo.md".to_string(),
+        content_line: 12,
         content: "\nSome Content.\n\n{% include file=\"examples/hello_world.rs\" %}\n".to_string(),
         todo: vec![
             "Add another article extending on the topic".to_string(),
@@ -793,8 +1820,11 @@ fn test_img_with_title() {
     let expected_page = Page {
         title: "Image with title".to_string(),
         timestamp: "2023-10-03T13:30:01".to_string(),
+        date: DateTime::parse_from_rfc3339("2023-10-03T13:30:01Z").unwrap(),
+        modified: DateTime::parse_from_rfc3339("2023-10-03T13:30:01Z").unwrap(),
         url_path: "img_with_title".to_string(),
         filename: "img_with_title.md".to_string(),
+        content_line: 8,
         content: "\n\n![a title](examples/files/code_maven_490_490.jpg)\n\n".to_string(),
         tags: vec!["img".to_string()],
         published: true,
@@ -808,6 +1838,108 @@ fn test_img_with_title() {
     assert_eq!(data, expected_page);
 }
 
+#[test]
+fn test_files_to_copy() {
+    let config = read_config("test_cases/demo").unwrap();
+    let page = read_md_file(&config, "test_cases/demo", "test_cases/demo/pages/files.md").unwrap();
+    let paths = get_files_to_copy(&config, "test_cases/demo", &vec![page.clone()]).unwrap();
+    assert_eq!(
+        paths,
+        vec![
+            PathBuf::from("examples/files/logo.svg"),
+            PathBuf::from("examples/files/notes.txt"),
+        ]
+    );
+
+    let video = Page {
+        content: String::from(r#"{% youtube id="K6EvVvYnjrY" file="videos/code-maven.mp4" %}"#),
+        ..page.clone()
+    };
+    assert_eq!(
+        get_files_to_copy(&config, "test_cases/demo", &vec![video]).unwrap(),
+        vec![PathBuf::from("videos/code-maven.mp4")]
+    );
+    let missing_video = Page {
+        content: String::from(r#"{% youtube id="K6EvVvYnjrY" file="/videos/intro.mp4" %}"#),
+        ..page.clone()
+    };
+    assert_eq!(
+        get_files_to_copy(&config, "test_cases/demo", &vec![missing_video])
+            .err()
+            .unwrap()
+            .to_string(),
+        "The video 'videos/intro.mp4' of the youtube tag does not exist in 'files.md' line 8"
+    );
+
+    let no_extension = Page {
+        content: String::from("![Logo](examples/files/logo)"),
+        ..page.clone()
+    };
+    assert_eq!(
+        get_files_to_copy(&config, "test_cases/demo", &vec![no_extension])
+            .err()
+            .unwrap()
+            .to_string(),
+        "No extension in 'examples/files/logo' in 'files.md' line 8"
+    );
+
+    let parentheses = Page {
+        content: String::from("[slides](files/talk_(1).pdf)"),
+        ..page.clone()
+    };
+    assert_eq!(
+        get_files_to_copy(&config, "test_cases/demo", &vec![parentheses])
+            .err()
+            .unwrap()
+            .to_string(),
+        "The file 'files/talk_(1).pdf' referenced in 'files.md' line 8 does not exist"
+    );
+
+    let zip = Page {
+        content: String::from(
+            "{% include dir=\"examples/project\" zip=true %}\n\n[project](/examples/project.zip)",
+        ),
+        ..page.clone()
+    };
+    assert!(get_files_to_copy(&config, "test_cases/demo", &vec![zip])
+        .unwrap()
+        .is_empty());
+    let no_zip = Page {
+        content: String::from(
+            "{% include dir=\"examples/project\" zip=false %}\n\n[project](/examples/project.zip)",
+        ),
+        ..page.clone()
+    };
+    assert_eq!(
+        get_files_to_copy(&config, "test_cases/demo", &vec![no_zip])
+            .err()
+            .unwrap()
+            .to_string(),
+        "The file 'examples/project.zip' referenced in 'files.md' line 10 does not exist"
+    );
+
+    let missing = Page {
+        content: format!("{}\n[slides](/files/talk.pdf)\n", page.content),
+        ..page
+    };
+    assert_eq!(
+        get_files_to_copy(&config, "test_cases/demo", &vec![missing])
+            .err()
+            .unwrap()
+            .to_string(),
+        "The file 'files/talk.pdf' referenced in 'files.md' line 17 does not exist"
+    );
+}
+
+#[test]
+fn test_is_external() {
+    assert!(is_external("https://code-maven.com/logo.png"));
+    assert!(is_external("//cdn.example.com/logo.png"));
+    assert!(is_external("mailto:foo@code-maven.com"));
+    assert!(!is_external("/examples/files/logo.png"));
+    assert!(!is_external("examples/files/logo.png"));
+}
+
 #[test]
 fn test_links() {
     let config = read_config("test_cases/demo").unwrap();
@@ -816,8 +1948,11 @@ fn test_links() {
     let expected_page = Page {
         title: "Links".to_string(),
         timestamp: "2023-10-01T12:30:01".to_string(),
+        date: DateTime::parse_from_rfc3339("2023-10-01T12:30:01Z").unwrap(),
+        modified: DateTime::parse_from_rfc3339("2023-10-01T12:30:01Z").unwrap(),
         url_path: "links".to_string(),
         filename: "links.md".to_string(),
+        content_line: 6,
         content: "\n* An [internal link](/with_todo) and more text.\n* An [external link](https://rust-digger.code-maven.com/) and more text.\n\n[sigils](/sigils) - another internal link to test the `show_related: false` in the front-matter of the sigils page\n[sub](/sub) - another internal link to test the `show_related: true` in the front-matter of the sigils page\n".to_string(),
         published: true,
         archive: true,
@@ -884,6 +2019,80 @@ fn test_bad_timestamp() {
     }
 }
 
+#[test]
+fn test_timestamps_with_timezone() {
+    let utc = FixedOffset::east_opt(0).unwrap();
+    let date = parse_timestamp("2015-10-11T12:30:01", &utc).unwrap();
+    assert_eq!(date.to_rfc3339(), "2015-10-11T12:30:01+00:00");
+
+    let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+    let date = parse_timestamp("2015-10-11T12:30:01", &offset).unwrap();
+    assert_eq!(date.to_rfc3339(), "2015-10-11T12:30:01+02:00");
+
+    let other = parse_timestamp("2015-10-11T10:30:01Z", &offset).unwrap();
+    assert_eq!(date, other);
+
+    let date = parse_timestamp("2015-10-11T12:30:01-05:00", &offset).unwrap();
+    assert_eq!(date.to_rfc3339(), "2015-10-11T12:30:01-05:00");
+}
+
+#[test]
+fn test_updated() {
+    let config = read_config("test_cases/demo").unwrap();
+    let data = read_md_file(&config, "test_cases/demo", "test_cases/demo/pages/about.md").unwrap();
+    assert_eq!(data.updated, "2024-01-02T10:00:00+02:00");
+    assert_eq!(data.date.to_rfc3339(), "2023-08-11T12:30:01+00:00");
+    assert_eq!(data.modified.to_rfc3339(), "2024-01-02T10:00:00+02:00");
+}
+
+#[test]
+fn test_permalink() {
+    let date = DateTime::parse_from_rfc3339("2024-01-02T10:00:00+02:00").unwrap();
+    assert_eq!(
+        expand_permalink("/:year/:month/:day/:slug/", &date, "hello").unwrap(),
+        "2024/01/02/hello/"
+    );
+    assert_eq!(
+        expand_permalink("/blog/:slug", &date, "hello").unwrap(),
+        "blog/hello"
+    );
+    assert_eq!(
+        expand_permalink("/:title/", &date, "hello").err().unwrap(),
+        "Unknown placeholder in permalink '/:title/'"
+    );
+}
+
+#[test]
+fn test_slug_and_permalink() {
+    let mut config = read_config("test_cases/demo").unwrap();
+    let mut page = Page {
+        date: DateTime::parse_from_rfc3339("2024-01-02T10:00:00Z").unwrap(),
+        ..Page::default()
+    };
+    assert_eq!(get_url_path(&config, &page, "about").unwrap(), "about");
+
+    page.slug = "other".to_string();
+    assert_eq!(get_url_path(&config, &page, "about").unwrap(), "other");
+
+    config.permalink = "/:year/:slug/".to_string();
+    assert_eq!(
+        get_url_path(&config, &page, "about").unwrap(),
+        "2024/other/"
+    );
+    assert_eq!(get_url_path(&config, &page, "index").unwrap(), "");
+
+    page.permalink = "/special".to_string();
+    assert_eq!(get_url_path(&config, &page, "about").unwrap(), "special");
+
+    let data = read_md_file(
+        &read_config("test_cases/demo").unwrap(),
+        "test_cases/demo",
+        "test_cases/demo/pages/permalink.md",
+    )
+    .unwrap();
+    assert_eq!(data.url_path, "2023/09/with-permalink/");
+}
+
 #[test]
 fn test_invalid_key() {
     //simple_logger::init_with_level(log::Level::Debug).unwrap();
@@ -914,6 +2123,7 @@ fn test_config_of_demo() {
             nickname: str!("szabgab"),
             picture: str!("szabgab.png"),
             text: str!(""),
+            twitter: str!("szabgab"),
         }]
     );
 }
@@ -937,6 +2147,7 @@ fn test_config_with_author_files() {
 teaches Rust, Python, git, CI, and testing.</p>
 "#
             ),
+            twitter: str!(""),
         }]
     );
 }
@@ -949,6 +2160,15 @@ fn test_config_with_duplicate_author() {
     assert_eq!(error, "nickname 'foobar' appears twice in config.yaml")
 }
 
+#[test]
+fn test_config_with_invalid_timezone() {
+    let error = read_config("test_cases/invalid_timezone/").err().unwrap();
+    assert_eq!(
+        error,
+        "Invalid timezone 'Asia/Jerusalem': input contains invalid characters"
+    );
+}
+
 #[test]
 fn test_config_with_invalid_field() {
     let error = read_config("test_cases/invalid_field_in_config/")
@@ -956,3 +2176,61 @@ fn test_config_with_invalid_field() {
         .unwrap();
     assert!(error.contains(r#"Invalid YAML format in "test_cases/invalid_field_in_config/config.yaml": unknown field `password`,"#))
 }
+
+#[test]
+fn test_tag_details() {
+    let config = read_config("test_cases/demo").unwrap();
+    assert_eq!(config.canonical_tag("RustLang"), "rust");
+    assert_eq!(config.canonical_tag("Perl"), "Perl");
+    assert!(config.tags.details[0]
+        .text
+        .contains("<strong>programming languages</strong>"));
+
+    let details = |name: &str, aliases: &[&str], parent: &str| TagDetails {
+        name: name.to_owned(),
+        title: String::new(),
+        description: String::new(),
+        aliases: aliases.iter().map(|alias| (*alias).to_owned()).collect(),
+        parent: parent.to_owned(),
+        text: String::new(),
+    };
+    assert_eq!(
+        read_tag_details(
+            vec![details("Rust", &[], ""), details("go", &["rust"], "")],
+            ""
+        )
+        .err()
+        .unwrap(),
+        "The tag 'rust' appears twice in config.yaml"
+    );
+    assert_eq!(
+        read_tag_details(vec![details("rust", &[], "Programming")], "")
+            .err()
+            .unwrap(),
+        "The parent 'programming' of the tag 'rust' is not in the tags of config.yaml"
+    );
+    assert_eq!(
+        read_tag_details(vec![details("a", &[], "b"), details("b", &[], "a")], "")
+            .err()
+            .unwrap(),
+        "The parents of the tag 'a' form a loop in config.yaml"
+    );
+}
+
+#[test]
+fn test_stable_hash() {
+    assert_eq!(
+        stable_hash(&[b"cargo run", b"src/main.rs"]),
+        stable_hash(&[b"cargo run", b"src/main.rs"])
+    );
+    assert_ne!(stable_hash(&[b"ab", b"c"]), stable_hash(&[b"a", b"bc"]));
+    assert_eq!(stable_hash(&[]).len(), 64);
+}
+
+#[test]
+fn test_escape() {
+    assert_eq!(
+        escape(r#"<a href="/x?a=1&b='2'">"#),
+        "&lt;a href=&quot;/x?a=1&amp;b=&#39;2&#39;&quot;&gt;"
+    );
+}
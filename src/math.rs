@@ -0,0 +1,99 @@
+use std::sync::LazyLock;
+
+use latex2mathml::{latex_to_mathml, DisplayStyle};
+use regex::{Captures, Regex};
+
+// The HTML the math extension of the markdown crate creates for `$...$` and `$$...$$`
+static MATH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?s)(<pre>)?<code class="language-math math-(inline|display)">(.*?)</code>(?:</pre>)?"#,
+    )
+    .unwrap()
+});
+
+// Short forms of commands that latex2mathml only knows by their long name
+static ALIASES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\\(le|ge)\b").unwrap());
+
+// Replace the math in the HTML by MathML so the browsers can show it without any JavaScript.
+pub fn render_math(html: &str) -> Result<String, String> {
+    let mut error = None;
+    let output = MATH.replace_all(html, |captures: &Captures| {
+        let latex = unescape(captures[3].trim());
+        let block = captures.get(1).is_some() || &captures[2] == "display";
+        match to_mathml(&latex, block) {
+            Ok(mathml) if block => format!(r#"<div class="math-display">{mathml}</div>"#),
+            Ok(mathml) => mathml,
+            Err(err) => {
+                error.get_or_insert(err);
+                String::new()
+            }
+        }
+    });
+
+    error.map_or_else(|| Ok(output.into_owned()), Err)
+}
+
+fn to_mathml(latex: &str, block: bool) -> Result<String, String> {
+    let style = if block {
+        DisplayStyle::Block
+    } else {
+        DisplayStyle::Inline
+    };
+    let aliased = ALIASES.replace_all(latex, r"\${1}q");
+    let mathml =
+        latex_to_mathml(&aliased, style).map_err(|err| format!("Invalid math '{latex}': {err}"))?;
+
+    // Unknown commands are reported inside the MathML
+    if let Some((_before, message)) = mathml.split_once("[PARSE ERROR: ") {
+        let message = message
+            .split_once("]<")
+            .map_or(message, |(message, _after)| message);
+        return Err(format!("Invalid math '{latex}': {message}"));
+    }
+
+    // The operators are not escaped by latex2mathml
+    Ok(mathml
+        .replace("<mo><</mo>", "<mo>&lt;</mo>")
+        .replace("<mo>></mo>", "<mo>&gt;</mo>")
+        .replace("<mo>&</mo>", "<mo>&amp;</mo>"))
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn inline_and_display() {
+        let html = render_math(
+            "<p>Euler: <code class=\"language-math math-inline\">e^{i\\pi} &lt; 1</code></p>\n<pre><code class=\"language-math math-display\">\\sum_{i=1}^n i \\le n^2\n</code></pre>",
+        )
+        .unwrap();
+        assert_eq!(
+            html,
+            "<p>Euler: <math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"inline\"><msup><mi>e</mi><mrow><mi>i</mi><mi>π</mi></mrow></msup><mo>&lt;</mo><mn>1</mn></math></p>\n<div class=\"math-display\"><math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"><munderover><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover><mi>i</mi><mo>≤</mo><msup><mi>n</mi><mn>2</mn></msup></math></div>"
+        );
+    }
+
+    #[test]
+    fn invalid_math() {
+        assert_eq!(
+            render_math("<code class=\"language-math math-inline\">\\frac{a</code>")
+                .err()
+                .unwrap(),
+            "Invalid math '\\frac{a': The token \"RBrace\" is expected, but the token \"EOF\" is found.\""
+        );
+        assert!(
+            render_math("<code class=\"language-math math-inline\">\\nosuch x</code>")
+                .err()
+                .unwrap()
+                .starts_with("Invalid math '\\nosuch x': Undefined")
+        );
+    }
+}
@@ -16,10 +16,12 @@ pub fn new_site(root: &str) -> Result<(), String> {
     let index_str = include_str!("../test_cases/skeleton/pages/index.md");
     let about_str = include_str!("../test_cases/skeleton/pages/about.md");
     let foobar_str = include_str!("../test_cases/skeleton/authors/foobar.md");
+    let foobar_picture = include_bytes!("../test_cases/skeleton/images/foobar.png");
 
     std::fs::create_dir_all(&path).unwrap();
     std::fs::create_dir_all(path.join("pages")).unwrap();
     std::fs::create_dir_all(path.join("authors")).unwrap();
+    std::fs::create_dir_all(path.join("images")).map_err(|err| err.to_string())?;
 
     writeln!(
         File::create(path.join(".gitignore")).unwrap(),
@@ -51,5 +53,8 @@ pub fn new_site(root: &str) -> Result<(), String> {
     )
     .unwrap();
 
+    std::fs::write(path.join("images").join("foobar.png"), foobar_picture)
+        .map_err(|err| err.to_string())?;
+
     Ok(())
 }
@@ -53,7 +53,7 @@ pub fn cm_sendgrid(root: &str, mail: &str, tofile: &str) -> Result<(), String> {
             &from,
             to_address,
             &page.title,
-            &markdown2html(&page.content),
+            &markdown2html(&page.content, false),
         );
     }
 
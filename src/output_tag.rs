@@ -0,0 +1,518 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use liquid_core::error::ResultLiquidReplaceExt as _;
+use liquid_core::model::Scalar;
+use liquid_core::Language;
+use liquid_core::Renderable;
+use liquid_core::Result;
+use liquid_core::Runtime;
+use liquid_core::ValueView as _;
+use liquid_core::{ParseTag, TagReflection, TagTokenIter};
+use serde::{Deserialize, Serialize};
+
+use crate::stable_hash;
+use crate::tag_args::parse_options;
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OutputTag;
+
+#[allow(clippy::missing_trait_methods)]
+impl TagReflection for OutputTag {
+    fn tag(&self) -> &'static str {
+        "output"
+    }
+
+    fn description(&self) -> &'static str {
+        ""
+    }
+}
+
+impl ParseTag for OutputTag {
+    fn parse(
+        &self,
+        arguments: TagTokenIter<'_>,
+        _options: &Language,
+    ) -> Result<Box<dyn Renderable>> {
+        let mut file = None;
+        let mut command = None;
+        let mut expected = None;
+
+        for (key, value) in parse_options(arguments, "output", &[])? {
+            match key.as_str() {
+                "file" => file = Some(value),
+                "command" => command = Some(value),
+                "expected" => expected = Some(value),
+                _ => {
+                    return Err(liquid_core::error::Error::with_msg(format!(
+                        "Unknown option '{key}' in output tag. Expected one of: file, command, expected"
+                    )))
+                }
+            }
+        }
+
+        let file = file.ok_or_else(|| liquid_core::error::Error::with_msg("\"file\" expected."))?;
+
+        Ok(Box::new(Output {
+            file,
+            command,
+            expected,
+        }))
+    }
+
+    fn reflection(&self) -> &dyn TagReflection {
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Output {
+    file: String,
+    command: Option<String>,
+    expected: Option<String>,
+}
+
+#[allow(clippy::missing_trait_methods)]
+impl Renderable for Output {
+    fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
+        let root = match runtime.get(&[Scalar::new("root")]) {
+            Ok(value) => value.to_kstr().into_string(),
+            Err(_) => {
+                return Err(liquid_core::error::Error::with_msg(
+                    "No value called 'root' was passed to the render function.",
+                ));
+            }
+        };
+
+        let output = run_example(
+            runtime,
+            &root,
+            &self.file,
+            self.command.as_deref(),
+            self.expected.as_deref(),
+        )?;
+        write!(writer, "{output}").replace("Failed to render")?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct RunResult {
+    stdout: String,
+    stderr: String,
+    status: Option<i32>,
+}
+
+impl RunResult {
+    fn text(&self) -> String {
+        let mut parts = vec![];
+        if !self.stdout.trim_end().is_empty() {
+            parts.push(self.stdout.trim_end().to_owned());
+        }
+        if !self.stderr.trim_end().is_empty() {
+            parts.push(self.stderr.trim_end().to_owned());
+        }
+        match self.status {
+            Some(0) => {}
+            Some(code) => parts.push(format!("Exit code: {code}")),
+            None => parts.push(String::from("Killed by a signal")),
+        }
+        parts.join("\n")
+    }
+}
+
+// Run the example (or take the result from the cache) and return the markdown showing its output.
+// In a dry run only the cached results are used.
+// If there is a file with the expected output (by default the name of the example followed by .out)
+// the build fails when the output is different.
+pub fn run_example(
+    runtime: &dyn Runtime,
+    root: &str,
+    file: &str,
+    command: Option<&str>,
+    expected: Option<&str>,
+) -> Result<String> {
+    let path = Path::new(root).join(file);
+    if !path.is_file() {
+        return Err(liquid_core::error::Error::with_msg(format!(
+            "Failed to read file {:?}",
+            path.display()
+        )));
+    }
+
+    let command = if let Some(command) = command {
+        command.to_owned()
+    } else {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        runtime
+            .get(&[
+                Scalar::new("run"),
+                Scalar::new("commands"),
+                Scalar::new(extension.clone()),
+            ])
+            .map(|value| value.to_kstr().into_string())
+            .map_err(|_err| {
+                liquid_core::error::Error::with_msg(format!(
+                    "No command to run '{file}'. Add one for '{extension}' to run.commands in config.yaml or use command=\"...\""
+                ))
+            })?
+    };
+
+    let cache_dir = runtime
+        .get(&[Scalar::new("run"), Scalar::new("cache")])
+        .map(|value| value.to_kstr().into_string())
+        .unwrap_or_default();
+    let cache_file = if cache_dir.is_empty() {
+        None
+    } else {
+        Some(
+            cache_path(
+                &Path::new(root).join(cache_dir),
+                &command,
+                Path::new(root),
+                &path,
+            )
+            .map_err(liquid_core::error::Error::with_msg)?,
+        )
+    };
+
+    let cached = cache_file
+        .as_ref()
+        .and_then(|cache_file| read_cache(cache_file));
+    let result = if let Some(result) = cached {
+        log::info!("Using cached output of '{file}'");
+        result
+    } else if is_dry_run(runtime) {
+        return Ok(String::new());
+    } else {
+        let result = run(&path, &command).map_err(liquid_core::error::Error::with_msg)?;
+        if let Some(cache_file) = &cache_file {
+            write_cache(cache_file, &result);
+        }
+        result
+    };
+    let text = result.text();
+
+    let expected_path = expected.map_or_else(
+        || {
+            let mut out = path.clone().into_os_string();
+            out.push(".out");
+            Some(PathBuf::from(out)).filter(|out| out.exists())
+        },
+        |expected| Some(Path::new(root).join(expected)),
+    );
+    if let Some(expected_path) = expected_path {
+        let expected_text = fs::read_to_string(&expected_path).replace(format!(
+            "Failed to read expected output {:?}",
+            expected_path.display()
+        ))?;
+        if expected_text.trim_end() != text {
+            return Err(liquid_core::error::Error::with_msg(format!(
+                "The output of '{file}' does not match {:?}\nExpected:\n{}\nReceived:\n{text}",
+                expected_path.display(),
+                expected_text.trim_end()
+            )));
+        }
+    }
+
+    Ok(format!("**Output:**\n```text\n{text}\n```\n"))
+}
+
+// The page is rendered again without running the examples and without writing files,
+// only to find the line of an error.
+pub fn is_dry_run(runtime: &dyn Runtime) -> bool {
+    runtime
+        .get(&[Scalar::new("dry_run")])
+        .is_ok_and(|value| value.query_state(liquid_core::model::State::Truthy))
+}
+
+// The `{file}` in the command is replaced by the name of the file.
+// The command runs in the directory of the file so `cargo run` finds the crate of the example.
+fn run(path: &Path, command: &str) -> Result<RunResult, String> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut words = command
+        .split_whitespace()
+        .map(|word| word.replace("{file}", &file_name));
+    let program = words
+        .next()
+        .ok_or_else(|| format!("Empty command to run '{}'", path.display()))?;
+
+    log::info!("Running '{command}' for '{}'", path.display());
+    let output = Command::new(&program)
+        .args(words)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| format!("Could not run '{command}' for '{}': {err}", path.display()))?;
+
+    Ok(RunResult {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        status: output.status.code(),
+    })
+}
+
+// The output is cached by the command and the file, or every file of the crate if the file is part
+// of one, so a change in e.g. the Cargo.toml or another module of an example crate runs the example again.
+fn cache_path(dir: &Path, command: &str, root: &Path, path: &Path) -> Result<PathBuf, String> {
+    let (base, mut files) = match crate_dir(root, path) {
+        Some(crate_dir) => {
+            let mut files = vec![];
+            list_files(&crate_dir, &mut files)?;
+            (crate_dir, files)
+        }
+        None => (
+            path.parent().unwrap_or(root).to_path_buf(),
+            vec![path.to_path_buf()],
+        ),
+    };
+    files.sort();
+
+    let mut parts = vec![command.as_bytes().to_vec()];
+    for file in files {
+        let name = file.strip_prefix(&base).unwrap_or(&file);
+        parts.push(name.to_string_lossy().as_bytes().to_vec());
+        parts.push(
+            fs::read(&file)
+                .map_err(|err| format!("Failed to read file {:?}: {err}", file.display()))?,
+        );
+    }
+    let parts = parts.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+    Ok(dir
+        .join("output")
+        .join(format!("{}.json", stable_hash(&parts))))
+}
+
+// The directory of the crate if the file is part of one within the site
+fn crate_dir(root: &Path, path: &Path) -> Option<PathBuf> {
+    path.parent()
+        .unwrap_or(root)
+        .ancestors()
+        .take_while(|ancestor| ancestor.starts_with(root))
+        .find(|ancestor| ancestor.join("Cargo.toml").is_file())
+        .map(Path::to_path_buf)
+}
+
+// The files of the directory and its subdirectories without the hidden files and the target/ of cargo
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|err| format!("Could not read directory '{}': {err}", dir.display()))?;
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with('.') || name == "target" {
+            continue;
+        }
+        if path.is_dir() {
+            list_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_cache(path: &Path) -> Option<RunResult> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn write_cache(path: &Path, result: &RunResult) {
+    let saved = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(path, serde_json::to_string_pretty(result).unwrap()));
+    if let Err(err) = saved {
+        log::warn!("Could not save the output cache {}: {err}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use liquid_core::parser;
+    use liquid_core::runtime;
+    use liquid_core::runtime::RuntimeBuilder;
+    use liquid_core::{object, Value};
+
+    fn render(text: &str, cache: &str) -> Result<String> {
+        render_with(text, cache, false)
+    }
+
+    fn render_with(text: &str, cache: &str, dry_run: bool) -> Result<String> {
+        let mut options = Language::default();
+        options
+            .tags
+            .register("output".to_string(), OutputTag.into());
+        let template = parser::parse(text, &options).map(runtime::Template::new)?;
+
+        let runtime = RuntimeBuilder::new().build();
+        runtime.set_global("root".into(), Value::scalar("test_cases/demo"));
+        runtime.set_global(
+            "run".into(),
+            Value::Object(object!({
+                "cache": cache,
+                "commands": {"sh": "sh {file}"},
+            })),
+        );
+        runtime.set_global("dry_run".into(), Value::scalar(dry_run));
+
+        template.render(&runtime)
+    }
+
+    #[test]
+    fn run_and_compare() {
+        let output = render(r#"{% output file="examples/count.sh" %}"#, "").unwrap();
+        assert_eq!(output, "**Output:**\n```text\n1\n2\n3\nDone\n```\n");
+    }
+
+    #[test]
+    fn output_mismatch() {
+        let error = render(
+            r#"{% output file="examples/count.sh" expected="examples/demo.yaml" %}"#,
+            "",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(error.starts_with("liquid: The output of 'examples/count.sh' does not match"));
+        assert!(error.contains("Expected:\nfield: value\nReceived:\n1\n2\n3\nDone"));
+    }
+
+    #[test]
+    fn missing_command() {
+        let error = render(r#"{% output file="examples/hello_world.rs" %}"#, "")
+            .err()
+            .unwrap()
+            .to_string();
+        assert_eq!(error, "liquid: No command to run 'examples/hello_world.rs'. Add one for 'rs' to run.commands in config.yaml or use command=\"...\"\n");
+    }
+
+    #[test]
+    fn status_and_stderr() {
+        let result = RunResult {
+            stdout: String::from("out\n"),
+            stderr: String::from("err\n"),
+            status: Some(2),
+        };
+        assert_eq!(result.text(), "out\nerr\nExit code: 2");
+    }
+
+    #[test]
+    fn cached_output() {
+        let dir = tempdir::TempDir::new("output").unwrap();
+        let cache = dir.path().join("cache");
+        let cache = cache.to_str().unwrap();
+        let path = cache_path(
+            Path::new("test_cases/demo").join(cache).as_path(),
+            "sh {file}",
+            Path::new("test_cases/demo"),
+            Path::new("test_cases/demo/examples/count.sh"),
+        )
+        .unwrap();
+
+        render(r#"{% output file="examples/count.sh" %}"#, cache).unwrap();
+        assert!(path.exists());
+
+        // The result comes from the cache so a different result in it shows up in the page
+        write_cache(
+            &path,
+            &RunResult {
+                stdout: String::from("1\n2\n3\nDone\n"),
+                stderr: String::from("from cache"),
+                status: Some(0),
+            },
+        );
+        let error = render(r#"{% output file="examples/count.sh" %}"#, cache)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("Received:\n1\n2\n3\nDone\nfrom cache"));
+    }
+
+    #[test]
+    fn dry_run() {
+        let dir = tempdir::TempDir::new("output").unwrap();
+        let cache = dir.path().join("cache");
+        let cache = cache.to_str().unwrap();
+        let tag = r#"{% output file="examples/count.sh" expected="examples/demo.yaml" %}"#;
+
+        // Nothing is run and nothing is written
+        assert_eq!(render_with(tag, cache, true).unwrap(), "");
+        assert!(!dir.path().join("cache").exists());
+
+        // The cached result fails the same way as the real run did
+        let error = render(tag, cache).err().unwrap().to_string();
+        assert_eq!(
+            render_with(tag, cache, true).err().unwrap().to_string(),
+            error
+        );
+    }
+
+    #[test]
+    fn cache_of_a_crate() {
+        let dir = tempdir::TempDir::new("crate").unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(dir.path().join("target")).unwrap();
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\n",
+        )
+        .unwrap();
+        fs::write(src.join("main.rs"), "fn main() {}\n").unwrap();
+        let main = src.join("main.rs");
+        assert_eq!(crate_dir(dir.path(), &main), Some(dir.path().to_path_buf()));
+        assert_eq!(crate_dir(&src, &main), None);
+        let path =
+            |command: &str| cache_path(Path::new("cache"), command, dir.path(), &main).unwrap();
+
+        let first = path("cargo run");
+        fs::write(dir.path().join("target").join("build.log"), "built").unwrap();
+        assert_eq!(path("cargo run"), first);
+
+        // A change in another file of the crate runs the example again
+        fs::write(src.join("lib.rs"), "pub fn answer() {}\n").unwrap();
+        let second = path("cargo run");
+        assert_ne!(second, first);
+        fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"other\"\n",
+        )
+        .unwrap();
+        assert_ne!(path("cargo run"), second);
+        assert_ne!(path("cargo run --release"), second);
+    }
+
+    #[test]
+    fn cache_of_a_file() {
+        let dir = tempdir::TempDir::new("examples").unwrap();
+        let script = dir.path().join("count.sh");
+        fs::write(&script, "echo 1\n").unwrap();
+        let path =
+            |command: &str| cache_path(Path::new("cache"), command, dir.path(), &script).unwrap();
+
+        // The other examples next to the file don't change its output
+        let first = path("bash {file}");
+        fs::write(dir.path().join("other.sh"), "echo 2\n").unwrap();
+        fs::create_dir_all(dir.path().join("project")).unwrap();
+        fs::write(dir.path().join("project").join("main.py"), "print(3)\n").unwrap();
+        assert_eq!(path("bash {file}"), first);
+
+        fs::write(&script, "echo 4\n").unwrap();
+        assert_ne!(path("bash {file}"), first);
+    }
+}
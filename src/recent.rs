@@ -26,13 +26,12 @@ fn list_recent(config: &Config, pages: Vec<Page>, recent: &str, url: &str) {
     let days: i64 = recent.parse().unwrap();
     let now: DateTime<Utc> = Utc::now();
     let date = now - TimeDelta::try_days(days).unwrap();
-    let date = date.format("%Y-%m-%dT%H:%M:%S").to_string();
     //println!("{:?}", pages);
 
     let filtered_pages: Vec<&Page> = pages
         .iter()
         .filter(|page| !page.url_path.is_empty() && page.url_path != "archive")
-        .filter(|page| page.timestamp > date)
+        .filter(|page| page.date > date)
         .collect();
 
     let template = include_str!("../templates/email.html");
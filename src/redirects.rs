@@ -0,0 +1,290 @@
+use std::collections::HashSet;
+use std::fs;
+use std::fs::File;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{escape, Config, Page, RedirectFormat};
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Redirect {
+    pub from: String,
+    pub to: String,
+}
+
+// Pages with a `redirect` field and pages that moved away from their previous URLs: the one
+// based on their filename (changed by a permalink or a slug) and the ones listed in `aliases`.
+pub fn collect_redirects(pages: &[Page]) -> Vec<Redirect> {
+    let url_paths = pages
+        .iter()
+        .map(|page| page.url_path.clone())
+        .collect::<HashSet<String>>();
+
+    let mut redirects: Vec<Redirect> = vec![];
+    for page in pages {
+        if page.url_path == "archive" {
+            continue;
+        }
+        if let Some(target) = &page.redirect {
+            redirects.push(Redirect {
+                from: format!("/{}", page.url_path),
+                to: target.clone(),
+            });
+            continue;
+        }
+
+        let mut stem = PathBuf::from(&page.filename);
+        stem.set_extension("");
+        let stem = stem.display().to_string();
+        let previous = if page.url_path.is_empty() {
+            vec![]
+        } else {
+            vec![stem]
+        };
+        for old in previous.iter().chain(&page.aliases) {
+            let old = old.trim_start_matches('/');
+            let from = format!("/{old}");
+            if old == page.url_path
+                || url_paths.contains(old)
+                || redirects.iter().any(|redirect| redirect.from == from)
+            {
+                continue;
+            }
+            redirects.push(Redirect {
+                from,
+                to: format!("/{}", page.url_path),
+            });
+        }
+    }
+
+    redirects
+}
+
+pub fn render_redirects(config: &Config, redirects: &[Redirect], outdir: &str, url: &str) {
+    log::info!("render_redirects");
+
+    // The web servers and Netlify serve the existing files ahead of their redirect rules
+    let server_side = config.redirects.iter().any(|format| {
+        matches!(
+            format,
+            RedirectFormat::Netlify | RedirectFormat::Nginx | RedirectFormat::Apache
+        )
+    });
+    if !server_side {
+        for redirect in redirects {
+            let path = Path::new(outdir).join(crate::web::get_output_file(
+                redirect.from.trim_start_matches('/'),
+            ));
+            log::info!("render redirect page path {}", path.display());
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            writeln!(
+                File::create(path).unwrap(),
+                "{}",
+                render_redirect_page(url, &redirect.to)
+            )
+            .unwrap();
+        }
+    }
+
+    for format in &config.redirects {
+        match format {
+            RedirectFormat::Netlify => save(outdir, "_redirects", &netlify(redirects)),
+            RedirectFormat::Nginx => save(outdir, "redirects.nginx.conf", &nginx(redirects)),
+            RedirectFormat::Apache => save(outdir, ".htaccess", &apache(redirects)),
+            RedirectFormat::Json => save(
+                outdir,
+                "redirects.json",
+                &serde_json::to_string_pretty(redirects).unwrap(),
+            ),
+        }
+    }
+}
+
+fn save(outdir: &str, filename: &str, content: &str) {
+    let path = Path::new(outdir).join(filename);
+    log::info!("saving file at {}", path.display());
+    writeln!(File::create(path).unwrap(), "{content}").unwrap();
+}
+
+// The target comes from the front matter so it is escaped everywhere it is used
+fn render_redirect_page(url: &str, target: &str) -> String {
+    let canonical = if target.starts_with('/') {
+        escape(&format!("{url}{target}"))
+    } else {
+        escape(target)
+    };
+    let target = escape(target);
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset="utf-8">
+    <title>Redirecting to {target}</title>
+    <link rel="canonical" href="{canonical}" />
+    <meta http-equiv="refresh" content="0; url={target}" />
+  </head>
+  <body>
+    <p>This page has moved to <a href="{target}">{target}</a>.</p>
+  </body>
+</html>"#
+    )
+}
+
+// For Netlify and Cloudflare Pages
+fn netlify(redirects: &[Redirect]) -> String {
+    redirects
+        .iter()
+        .map(|redirect| format!("{} {} 301", redirect.from, redirect.to))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Include it in the http block and use it in the server block:
+// if ($redirect_uri) { return 301 $redirect_uri; }
+// The paths are quoted so spaces, semicolons and braces in them can't break the config.
+fn nginx(redirects: &[Redirect]) -> String {
+    let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+    let entries = redirects
+        .iter()
+        .map(|redirect| format!("    {} {};", quote(&redirect.from), quote(&redirect.to)))
+        .collect::<Vec<String>>()
+        .join("\n");
+    format!("map $uri $redirect_uri {{\n{entries}\n}}")
+}
+
+fn apache(redirects: &[Redirect]) -> String {
+    redirects
+        .iter()
+        .map(|redirect| format!("Redirect 301 {} {}", redirect.from, redirect.to))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_redirects() -> Vec<Redirect> {
+        vec![
+            Redirect {
+                from: "/old".to_string(),
+                to: "/new".to_string(),
+            },
+            Redirect {
+                from: "/away".to_string(),
+                to: "https://rust.code-maven.com/".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn collect() {
+        let pages = vec![
+            Page {
+                url_path: "old".to_string(),
+                filename: "old.md".to_string(),
+                redirect: Some("/new".to_string()),
+                ..Page::default()
+            },
+            Page {
+                url_path: "2024/moved/".to_string(),
+                filename: "moved.md".to_string(),
+                ..Page::default()
+            },
+            Page {
+                url_path: "same".to_string(),
+                filename: "same.md".to_string(),
+                ..Page::default()
+            },
+        ];
+        assert_eq!(
+            collect_redirects(&pages),
+            vec![
+                Redirect {
+                    from: "/old".to_string(),
+                    to: "/new".to_string(),
+                },
+                Redirect {
+                    from: "/moved".to_string(),
+                    to: "/2024/moved/".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn changed_slug() {
+        // The slug was `old-name` with the `/:year/:slug/` permalink
+        let pages = vec![Page {
+            url_path: "2024/new-name/".to_string(),
+            filename: "post.md".to_string(),
+            slug: "new-name".to_string(),
+            aliases: vec!["/2024/old-name/".to_string(), "post".to_string()],
+            ..Page::default()
+        }];
+        assert_eq!(
+            collect_redirects(&pages),
+            vec![
+                Redirect {
+                    from: "/post".to_string(),
+                    to: "/2024/new-name/".to_string(),
+                },
+                Redirect {
+                    from: "/2024/old-name/".to_string(),
+                    to: "/2024/new-name/".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn redirect_maps() {
+        let redirects = get_redirects();
+        assert_eq!(
+            netlify(&redirects),
+            "/old /new 301\n/away https://rust.code-maven.com/ 301"
+        );
+        assert_eq!(
+            nginx(&redirects),
+            "map $uri $redirect_uri {\n    \"/old\" \"/new\";\n    \"/away\" \"https://rust.code-maven.com/\";\n}"
+        );
+        assert_eq!(
+            apache(&redirects),
+            "Redirect 301 /old /new\nRedirect 301 /away https://rust.code-maven.com/"
+        );
+    }
+
+    #[test]
+    fn redirect_page() {
+        let html = render_redirect_page("https://rust.code-maven.com", "/new");
+        assert!(html.contains(r#"<link rel="canonical" href="https://rust.code-maven.com/new" />"#));
+        assert!(html.contains(r#"<meta http-equiv="refresh" content="0; url=/new" />"#));
+        assert!(html.contains(r#"<a href="/new">/new</a>"#));
+
+        let html = render_redirect_page(
+            "https://rust.code-maven.com",
+            r#"/x"><script>alert(1)</script>?a=1&b=2"#,
+        );
+        assert!(!html.contains("<script>"));
+        assert!(html.contains(
+            r#"<a href="/x&quot;&gt;&lt;script&gt;alert(1)&lt;/script&gt;?a=1&amp;b=2">"#
+        ));
+        assert!(html.contains(r#"content="0; url=/x&quot;&gt;"#));
+        assert!(html.contains("<title>Redirecting to /x&quot;&gt;&lt;script&gt;"));
+    }
+
+    #[test]
+    fn quoted_nginx_map() {
+        let redirects = vec![Redirect {
+            from: String::from("/a b;"),
+            to: String::from(r#"/c"d\e"#),
+        }];
+        assert_eq!(
+            nginx(&redirects),
+            "map $uri $redirect_uri {\n    \"/a b;\" \"/c\\\"d\\\\e\";\n}"
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{ConfigRelated, Page};
+
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\p{Alphabetic}\w+").unwrap());
+
+// Words that are in almost every page and say nothing about its topic
+const STOP_WORDS: [&str; 32] = [
+    "the", "and", "for", "are", "but", "not", "you", "all", "any", "can", "has", "have", "was",
+    "one", "our", "out", "this", "that", "with", "from", "they", "will", "what", "when", "which",
+    "there", "their", "then", "than", "into", "also", "its",
+];
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Related {
+    pub title: String,
+    pub url_path: String,
+}
+
+// The pages most similar to each page by their tags, series, author and text, the most similar first.
+pub fn collect_related(config: &ConfigRelated, pages: Vec<Page>) -> Vec<Page> {
+    log::info!("collect_related");
+
+    let candidates = pages
+        .iter()
+        .filter(|page| page.published && page.redirect.is_none() && page.url_path != "archive")
+        .collect::<Vec<_>>();
+    let vectors = tf_idf(&candidates);
+    // On a site with a single author every pair would get the points of the same author
+    let authors = candidates
+        .iter()
+        .map(|page| page.author.as_str())
+        .collect::<HashSet<_>>();
+    let author_weight = if authors.len() > 1 { config.author } else { 0 };
+
+    let related = candidates
+        .iter()
+        .enumerate()
+        .map(|(index, page)| {
+            let mut scores = candidates
+                .iter()
+                .enumerate()
+                .filter(|(other_index, _other)| *other_index != index)
+                .map(|(other_index, other)| {
+                    let similarity = cosine(&vectors[index], &vectors[other_index]);
+                    (
+                        score(config, author_weight, page, other, similarity),
+                        *other,
+                    )
+                })
+                .filter(|(points, _other)| *points > 0)
+                .collect::<Vec<_>>();
+            scores.sort_by(|(first_points, first), (second_points, second)| {
+                second_points
+                    .cmp(first_points)
+                    .then_with(|| second.date.cmp(&first.date))
+                    .then_with(|| first.title.cmp(&second.title))
+            });
+            let related = scores
+                .into_iter()
+                .take(config.count)
+                .map(|(_points, other)| Related {
+                    title: other.title.clone(),
+                    url_path: other.url_path.clone(),
+                })
+                .collect::<Vec<_>>();
+            (page.url_path.clone(), related)
+        })
+        .collect::<HashMap<_, _>>();
+
+    pages
+        .into_iter()
+        .map(|mut page| {
+            if let Some(related) = related.get(&page.url_path) {
+                page.related.clone_from(related);
+            }
+            page
+        })
+        .collect()
+}
+
+// Each shared tag, the same series and the same author are worth 100 points times their weight.
+// The text adds its similarity in percents times its weight.
+fn score(
+    config: &ConfigRelated,
+    author_weight: u64,
+    page: &Page,
+    other: &Page,
+    similarity: u64,
+) -> u64 {
+    let tags = page
+        .tags
+        .iter()
+        .map(|tag| tag.to_lowercase())
+        .collect::<HashSet<_>>();
+    let shared = other
+        .tags
+        .iter()
+        .map(|tag| tag.to_lowercase())
+        .filter(|tag| tags.contains(tag))
+        .count();
+    let same = |first: &str, second: &str| u64::from(!first.is_empty() && first == second);
+
+    u64::try_from(shared).unwrap_or(u64::MAX) * config.tags * 100
+        + same(&page.series, &other.series) * config.series * 100
+        + same(&page.author, &other.author) * author_weight * 100
+        + similarity * config.text
+}
+
+fn words(text: &str) -> Vec<String> {
+    WORD.find_iter(text)
+        .map(|word| word.as_str().to_lowercase())
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+// The TF-IDF weight of each word of each page, normalized to a unit vector
+#[expect(clippy::float_arithmetic)]
+fn tf_idf(pages: &[&Page]) -> Vec<HashMap<String, f64>> {
+    let counts = pages
+        .iter()
+        .map(|page| {
+            let mut counts: HashMap<String, u32> = HashMap::new();
+            for word in words(&format!("{} {}", page.title, page.content)) {
+                *counts.entry(word).or_insert(0) += 1;
+            }
+            counts
+        })
+        .collect::<Vec<_>>();
+
+    let mut documents: HashMap<&str, u32> = HashMap::new();
+    for word in counts.iter().flat_map(HashMap::keys) {
+        *documents.entry(word).or_insert(0) += 1;
+    }
+    let total = f64::from(u32::try_from(pages.len()).unwrap_or(u32::MAX));
+
+    counts
+        .iter()
+        .map(|counts| {
+            let weights = counts
+                .iter()
+                .map(|(word, count)| {
+                    let idf = (total / f64::from(documents[word.as_str()])).ln();
+                    (word.clone(), f64::from(*count) * idf)
+                })
+                .collect::<Vec<_>>();
+            let norm = weights
+                .iter()
+                .map(|(_word, weight)| weight * weight)
+                .sum::<f64>()
+                .sqrt();
+            weights
+                .into_iter()
+                .map(|(word, weight)| (word, if norm > 0.0 { weight / norm } else { weight }))
+                .collect()
+        })
+        .collect()
+}
+
+// The cosine similarity of two unit vectors in percents
+#[expect(clippy::float_arithmetic)]
+#[expect(clippy::as_conversions)]
+#[expect(clippy::cast_possible_truncation)]
+#[expect(clippy::cast_sign_loss)]
+fn cosine(first: &HashMap<String, f64>, second: &HashMap<String, f64>) -> u64 {
+    let product = first
+        .iter()
+        .filter_map(|(word, value)| second.get(word).map(|other| value * other))
+        .sum::<f64>();
+    (product.clamp(0.0, 1.0) * 100.0).round() as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn related(pages: &[Page], url_path: &str) -> Vec<String> {
+        pages
+            .iter()
+            .find(|page| page.url_path == url_path)
+            .unwrap()
+            .related
+            .iter()
+            .map(|related| related.url_path.clone())
+            .collect()
+    }
+
+    #[test]
+    fn scores() {
+        let config = ConfigRelated {
+            count: 2,
+            ..ConfigRelated::default()
+        };
+        let pages = collect_related(
+            &config,
+            vec![
+                Page {
+                    content: String::from("Traits and generics in Rust"),
+                    ..Page::for_test("traits", &["rust"])
+                },
+                Page {
+                    content: String::from("Generic functions and traits"),
+                    ..Page::for_test("generics", &["rust"])
+                },
+                Page {
+                    content: String::from("Borrowing and lifetimes"),
+                    ..Page::for_test("lifetimes", &["rust"])
+                },
+                Page {
+                    content: String::from("Decorators wrap functions"),
+                    ..Page::for_test("decorators", &["python"])
+                },
+                Page {
+                    series: String::from("intro"),
+                    content: String::from("Hello World"),
+                    ..Page::for_test("hello", &[])
+                },
+                Page {
+                    series: String::from("intro"),
+                    content: String::from("Variables"),
+                    ..Page::for_test("variables", &[])
+                },
+                Page {
+                    published: false,
+                    content: String::from("Traits and generics"),
+                    ..Page::for_test("draft", &["rust"])
+                },
+            ],
+        );
+
+        // The shared tag and the shared words
+        assert_eq!(related(&pages, "traits"), vec!["generics", "lifetimes"]);
+        // Only the shared words
+        assert_eq!(related(&pages, "decorators"), vec!["generics"]);
+        assert_eq!(related(&pages, "hello"), vec!["variables"]);
+        assert!(related(&pages, "draft").is_empty());
+    }
+
+    #[test]
+    fn single_author() {
+        let config = ConfigRelated {
+            count: 1,
+            ..ConfigRelated::default()
+        };
+        let pages = vec![
+            Page {
+                author: String::from("foo"),
+                content: String::from("Traits and generics"),
+                ..Page::for_test("traits", &[])
+            },
+            Page {
+                author: String::from("foo"),
+                content: String::from("Generic functions and traits"),
+                ..Page::for_test("generics", &[])
+            },
+            Page {
+                author: String::from("foo"),
+                content: String::from("Hello World"),
+                ..Page::for_test("hello", &[])
+            },
+        ];
+
+        // The same author is not a reason to relate two pages on a site with a single author
+        let single = collect_related(&config, pages.clone());
+        assert_eq!(related(&single, "traits"), vec!["generics"]);
+        assert!(related(&single, "hello").is_empty());
+
+        let mut pages = pages;
+        pages.push(Page {
+            author: String::from("bar"),
+            content: String::from("Other"),
+            ..Page::for_test("other", &[])
+        });
+        let several = collect_related(&config, pages);
+        assert_eq!(related(&several, "hello"), vec!["generics"]);
+    }
+
+    #[test]
+    fn similarity() {
+        let first = Page {
+            content: String::from("The borrow checker checks the borrowing"),
+            ..Page::for_test("a", &[])
+        };
+        let second = Page {
+            content: String::from("Borrowing and the borrow checker"),
+            ..Page::for_test("b", &[])
+        };
+        let third = Page {
+            content: String::from("Async functions"),
+            ..Page::for_test("c", &[])
+        };
+        let vectors = tf_idf(&[&first, &second, &third]);
+        assert_eq!(cosine(&vectors[0], &vectors[0]), 100);
+        assert!(cosine(&vectors[0], &vectors[1]) > 50);
+        assert_eq!(cosine(&vectors[0], &vectors[2]), 0);
+    }
+}
@@ -0,0 +1,177 @@
+use serde_json::{json, Map, Value};
+
+use crate::{topath, Author, Config, Page};
+
+// The JSON-LD structured data of a page: the page as a BlogPosting (or as an Article if it is not
+// listed in the archive) and its BreadcrumbList. The main page describes the WebSite instead.
+pub fn page_json_ld(config: &Config, page: &Page, author: &Author, image: Option<&str>) -> String {
+    let items = if page.url_path.is_empty() {
+        vec![website(config)]
+    } else {
+        vec![
+            article(config, page, author, image),
+            breadcrumbs(config, page),
+        ]
+    };
+
+    // A </script> in a title must not end the script element
+    serde_json::to_string(&items).unwrap().replace("</", "<\\/")
+}
+
+fn website(config: &Config) -> Value {
+    let mut website = Map::new();
+    website.insert("@context".into(), json!("https://schema.org"));
+    website.insert("@type".into(), json!("WebSite"));
+    website.insert("name".into(), json!(config.site_name));
+    website.insert("url".into(), json!(format!("{}/", config.url)));
+    if !config.search.is_empty() {
+        website.insert(
+            "potentialAction".into(),
+            json!({
+                "@type": "SearchAction",
+                "target": {
+                    "@type": "EntryPoint",
+                    "urlTemplate": config.search,
+                },
+                "query-input": "required name=search_term_string",
+            }),
+        );
+    }
+    Value::Object(website)
+}
+
+fn article(config: &Config, page: &Page, author: &Author, image: Option<&str>) -> Value {
+    let url = format!("{}/{}", config.url, page.url_path);
+
+    let mut article = Map::new();
+    article.insert("@context".into(), json!("https://schema.org"));
+    article.insert(
+        "@type".into(),
+        json!(if page.archive {
+            "BlogPosting"
+        } else {
+            "Article"
+        }),
+    );
+    article.insert("headline".into(), json!(page.title));
+    if !page.description.is_empty() {
+        article.insert("description".into(), json!(page.description));
+    }
+    article.insert("url".into(), json!(url));
+    article.insert("mainEntityOfPage".into(), json!(url));
+    article.insert("datePublished".into(), json!(page.date.to_rfc3339()));
+    article.insert("dateModified".into(), json!(page.modified.to_rfc3339()));
+    if !page.tags.is_empty() {
+        article.insert("keywords".into(), json!(page.tags));
+    }
+    if !author.name.is_empty() {
+        article.insert(
+            "author".into(),
+            json!({"@type": "Person", "name": author.name}),
+        );
+    }
+    if let Some(image) = image {
+        article.insert("image".into(), json!(image));
+    }
+    if !config.site_name.is_empty() {
+        article.insert(
+            "publisher".into(),
+            json!({"@type": "Organization", "name": config.site_name}),
+        );
+    }
+    Value::Object(article)
+}
+
+// Home > the first tag of the page > the page
+fn breadcrumbs(config: &Config, page: &Page) -> Value {
+    let home = if config.site_name.is_empty() {
+        "Home"
+    } else {
+        &config.site_name
+    };
+    let mut crumbs = vec![(home.to_owned(), format!("{}/", config.url))];
+    if let Some(tag) = page.tags.first() {
+        crumbs.push((
+            tag.clone(),
+            format!("{}/tags/{}", config.url, topath(&tag.to_lowercase())),
+        ));
+    }
+    crumbs.push((
+        page.title.clone(),
+        format!("{}/{}", config.url, page.url_path),
+    ));
+
+    json!({
+        "@context": "https://schema.org",
+        "@type": "BreadcrumbList",
+        "itemListElement": crumbs
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, item))| json!({
+                "@type": "ListItem",
+                "position": index + 1,
+                "name": name,
+                "item": item,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::read_config;
+
+    #[test]
+    fn blog_posting() {
+        let config = read_config("test_cases/demo").unwrap();
+        let page = Page {
+            title: String::from("Hello </script>"),
+            url_path: String::from("hello"),
+            tags: vec![String::from("Rust")],
+            archive: true,
+            ..Page::default()
+        };
+        let author = config.authors[0].clone();
+        let json: Value = serde_json::from_str(&page_json_ld(
+            &config,
+            &page,
+            &author,
+            Some("https://rust.code-maven.com/img/hello.png"),
+        ))
+        .unwrap();
+
+        assert_eq!(json[0]["@type"], "BlogPosting");
+        assert_eq!(json[0]["headline"], "Hello </script>");
+        assert_eq!(json[0]["author"]["name"], "Gabor Szabo");
+        assert_eq!(
+            json[0]["image"],
+            "https://rust.code-maven.com/img/hello.png"
+        );
+        assert_eq!(json[0]["url"], "https://rust.code-maven.com/hello");
+        assert_eq!(json[1]["@type"], "BreadcrumbList");
+        assert_eq!(
+            json[1]["itemListElement"][1]["item"],
+            "https://rust.code-maven.com/tags/rust"
+        );
+        assert_eq!(json[1]["itemListElement"][2]["position"], 3);
+        assert!(!page_json_ld(&config, &page, &author, None).contains("</script>"));
+    }
+
+    #[test]
+    fn website_with_search() {
+        let mut config = read_config("test_cases/demo").unwrap();
+        config.search = String::from("https://rust.code-maven.com/search?q={search_term_string}");
+        let author = config.authors[0].clone();
+        let json: Value =
+            serde_json::from_str(&page_json_ld(&config, &Page::default(), &author, None)).unwrap();
+
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["@type"], "WebSite");
+        assert_eq!(
+            json[0]["potentialAction"]["target"]["urlTemplate"],
+            "https://rust.code-maven.com/search?q={search_term_string}"
+        );
+    }
+}
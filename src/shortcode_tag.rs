@@ -0,0 +1,285 @@
+use core::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use liquid_core::error::ResultLiquidReplaceExt as _;
+use liquid_core::model::{Object, Value};
+use liquid_core::runtime::{StackFrame, Template};
+use liquid_core::Language;
+use liquid_core::Renderable;
+use liquid_core::Result;
+use liquid_core::Runtime;
+use liquid_core::{ParseTag, TagReflection, TagTokenIter};
+
+use crate::tag_args::parse_options;
+
+// The tags of the Liquid standard library. A shortcode with one of these names
+// can only be used as {% shortcode name="..." %}
+const LIQUID_TAGS: [&str; 17] = [
+    "assign",
+    "break",
+    "capture",
+    "case",
+    "comment",
+    "continue",
+    "cycle",
+    "decrement",
+    "for",
+    "if",
+    "ifchanged",
+    "include",
+    "increment",
+    "raw",
+    "render",
+    "tablerow",
+    "unless",
+];
+
+thread_local! {
+    // The names of the shortcodes being parsed. A shortcode that uses itself, directly or through
+    // other shortcodes, would be parsed again and again.
+    static PARSING: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+// `{% shortcode name="button" href="/x" %}` if `name` is None,
+// `{% button href="/x" %}` for the shortcode called "button" otherwise.
+#[derive(Clone, Debug)]
+pub struct ShortcodeTag {
+    name: Option<&'static str>,
+    // The Liquid snippets in the shortcodes/ directory of the site by their name
+    shortcodes: HashMap<String, String>,
+}
+
+// Read `<root>/shortcodes/<name>.html` and return the tags to register: one for each shortcode
+// and the generic `shortcode` tag. The names of the built-in tags cannot be reused.
+pub fn read_shortcodes(root: &str, builtin: &[&str]) -> Result<Vec<ShortcodeTag>, String> {
+    let dir = Path::new(root).join("shortcodes");
+    let mut shortcodes = HashMap::new();
+
+    if dir.is_dir() {
+        let entries = fs::read_dir(&dir)
+            .map_err(|err| format!("Could not read directory {:?}: {err}", dir.display()))?;
+        for entry in entries.filter_map(core::result::Result::ok) {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("html") {
+                continue;
+            }
+            let name = path
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if !is_valid_name(&name) {
+                return Err(format!(
+                    "Invalid shortcode name {:?}. Use only letters, digits, _ and - and start with a letter",
+                    path.display()
+                ));
+            }
+            let source = fs::read_to_string(&path)
+                .map_err(|err| format!("Could not read {:?}: {err}", path.display()))?;
+            shortcodes.insert(name, source.trim_end().to_owned());
+        }
+    }
+
+    let mut tags = vec![ShortcodeTag {
+        name: None,
+        shortcodes: shortcodes.clone(),
+    }];
+    let mut names = shortcodes.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        if name == "shortcode"
+            || builtin.contains(&name.as_str())
+            || LIQUID_TAGS.contains(&name.as_str())
+        {
+            log::warn!(
+                "The shortcode '{name}' has the same name as a built-in tag. Use it as {{% shortcode name=\"{name}\" %}}"
+            );
+            continue;
+        }
+        // The tags need a static name. They live till the end of the program anyway.
+        let name: &'static str = Box::leak(name.clone().into_boxed_str());
+        tags.push(ShortcodeTag {
+            name: Some(name),
+            shortcodes: shortcodes.clone(),
+        });
+    }
+
+    Ok(tags)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    name.starts_with(|chr: char| chr.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|chr| chr.is_ascii_alphanumeric() || chr == '_' || chr == '-')
+}
+
+#[allow(clippy::missing_trait_methods)]
+impl TagReflection for ShortcodeTag {
+    fn tag(&self) -> &'static str {
+        self.name.unwrap_or("shortcode")
+    }
+
+    fn description(&self) -> &'static str {
+        ""
+    }
+}
+
+impl ParseTag for ShortcodeTag {
+    fn parse(
+        &self,
+        arguments: TagTokenIter<'_>,
+        options: &Language,
+    ) -> Result<Box<dyn Renderable>> {
+        let tag = self.tag();
+        let mut arguments = parse_options(arguments, tag, &[])?;
+
+        let name = if let Some(name) = self.name {
+            name.to_owned()
+        } else {
+            let index = arguments
+                .iter()
+                .position(|(key, _value)| key == "name")
+                .ok_or_else(|| {
+                    liquid_core::error::Error::with_msg("\"name\" expected in shortcode tag.")
+                })?;
+            arguments.remove(index).1
+        };
+
+        let source = self.shortcodes.get(&name).ok_or_else(|| {
+            let mut names = self.shortcodes.keys().cloned().collect::<Vec<_>>();
+            names.sort();
+            liquid_core::error::Error::with_msg(format!(
+                "Unknown shortcode '{name}'. Available shortcodes: {}",
+                names.join(", ")
+            ))
+        })?;
+
+        let cycle = PARSING.with_borrow(|names| {
+            names
+                .iter()
+                .position(|parsing| *parsing == name)
+                .map(|start| names[start..].join(" -> "))
+        });
+        if let Some(cycle) = cycle {
+            return Err(liquid_core::error::Error::with_msg(format!(
+                "The shortcode '{name}' uses itself: {cycle} -> {name}"
+            )));
+        }
+
+        // The snippet can use the other tags, including the other shortcodes
+        PARSING.with_borrow_mut(|names| names.push(name.clone()));
+        let template = liquid_core::parser::parse(source, options);
+        PARSING.with_borrow_mut(Vec::pop);
+        let template = template
+            .map(Template::new)
+            .map_err(|err| err.trace(format!("shortcodes/{name}.html")))?;
+
+        let mut variables = Object::new();
+        for (key, value) in arguments {
+            variables.insert(key.into(), Value::scalar(value));
+        }
+
+        Ok(Box::new(Shortcode {
+            template,
+            variables,
+        }))
+    }
+
+    fn reflection(&self) -> &dyn TagReflection {
+        self
+    }
+}
+
+#[derive(Debug)]
+struct Shortcode {
+    template: Template,
+    variables: Object,
+}
+
+#[allow(clippy::missing_trait_methods)]
+impl Renderable for Shortcode {
+    fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
+        // The arguments of the tag are variables in the snippet, the globals are also available
+        let scope = StackFrame::new(runtime, &self.variables);
+        let html = self.template.render(&scope)?;
+        write!(writer, "{html}").replace("Failed to render")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn render(text: &str) -> Result<String> {
+        render_with("test_cases/demo", text)
+    }
+
+    fn render_with(root: &str, text: &str) -> Result<String> {
+        let parser = read_shortcodes(root, &[])
+            .unwrap()
+            .into_iter()
+            .fold(liquid::ParserBuilder::with_stdlib(), |builder, tag| {
+                builder.tag(tag)
+            })
+            .build()?;
+
+        parser.parse(text)?.render(&liquid::Object::new())
+    }
+
+    #[test]
+    fn named_and_generic() {
+        let html = r#"<a class="button is-primary" href="/x">Go</a>"#;
+        assert_eq!(render(r#"{% button href="/x" text="Go" %}"#).unwrap(), html);
+        assert_eq!(
+            render(r#"{% shortcode name="button" href="/x" text="Go" %}"#).unwrap(),
+            html
+        );
+    }
+
+    #[test]
+    fn unknown_shortcode() {
+        let error = render(r#"{% shortcode name="nosuch" %}"#)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.starts_with("liquid: Unknown shortcode 'nosuch'. Available shortcodes: "));
+    }
+
+    #[test]
+    fn cycles() {
+        let error = render_with("test_cases/shortcode_loop", "{% loopy %}")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.starts_with("liquid: The shortcode 'loopy' uses itself: loopy -> loopy\n"));
+
+        let error = render_with("test_cases/shortcode_loop", "{% ping %}")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            error.starts_with("liquid: The shortcode 'ping' uses itself: ping -> pong -> ping\n")
+        );
+
+        // The stack is empty again after the error
+        let error = render_with("test_cases/shortcode_loop", "{% pong %}")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            error.starts_with("liquid: The shortcode 'pong' uses itself: pong -> ping -> pong\n")
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_valid_name("call-to-action"));
+        assert!(!is_valid_name("1st"));
+        assert!(!is_valid_name("with space"));
+    }
+}
@@ -0,0 +1,252 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::Utc;
+use image::imageops::FilterType;
+use image::ImageFormat;
+
+use crate::{Config, ConfigIcon, ConfigSecurity, Page};
+
+// The sizes of the PNG icons created from the icon of the site and their names
+const ICONS: [(u32, &str); 5] = [
+    (16, "favicon-16x16.png"),
+    (32, "favicon-32x32.png"),
+    (180, "apple-touch-icon.png"),
+    (192, "icon-192x192.png"),
+    (512, "icon-512x512.png"),
+];
+
+pub fn render_robots_txt(config: &Config, outdir: &str) -> Result<(), String> {
+    log::info!("render_robots_txt");
+
+    let mut lines = vec![format!("Sitemap: {}/sitemap.xml", config.url)];
+    if config.robots.is_empty() {
+        lines.extend([String::new(), String::from("User-agent: *")]);
+    }
+    for robots in &config.robots {
+        lines.extend([String::new(), format!("User-agent: {}", robots.user_agent)]);
+        lines.extend(
+            robots
+                .disallow
+                .iter()
+                .map(|path| format!("Disallow: {path}")),
+        );
+        lines.extend(robots.allow.iter().map(|path| format!("Allow: {path}")));
+        lines.extend(
+            robots
+                .crawl_delay
+                .map(|delay| format!("Crawl-delay: {delay}")),
+        );
+    }
+
+    write(
+        &Path::new(outdir).join("robots.txt"),
+        &format!("{}\n\n", lines.join("\n")),
+    )
+}
+
+// The authors of the site and the date of the last change, see https://humanstxt.org/
+pub fn render_humans_txt(config: &Config, pages: &[Page], outdir: &str) -> Result<(), String> {
+    log::info!("render_humans_txt");
+
+    let mut team = vec![String::from("/* TEAM */")];
+    for author in &config.authors {
+        team.push(format!("Name: {}", author.name));
+        if !author.twitter.is_empty() {
+            team.push(format!("Twitter: @{}", author.twitter));
+        }
+        team.push(String::new());
+    }
+    let updated = pages
+        .iter()
+        .filter(|page| page.published)
+        .map(|page| page.modified)
+        .max()
+        .unwrap_or_default();
+
+    let text = format!(
+        "{}\n/* SITE */\nLast update: {}\nSoftware: Code Maven https://github.com/szabgab/code-maven.rs\n",
+        team.join("\n"),
+        updated.format("%Y-%m-%d")
+    );
+    write(&Path::new(outdir).join("humans.txt"), &text)
+}
+
+// https://www.rfc-editor.org/rfc/rfc9116
+pub fn render_security_txt(
+    config: &Config,
+    security: &ConfigSecurity,
+    outdir: &str,
+) -> Result<(), String> {
+    log::info!("render_security_txt");
+
+    if security.expires < Utc::now() {
+        log::warn!(
+            "The expiration date of the security.txt {} is in the past",
+            security.expires
+        );
+    }
+
+    let mut lines = security
+        .contact
+        .iter()
+        .map(|contact| {
+            if contact.contains(':') {
+                format!("Contact: {contact}")
+            } else {
+                format!("Contact: mailto:{contact}")
+            }
+        })
+        .collect::<Vec<_>>();
+    lines.push(format!("Expires: {}", security.expires.to_rfc3339()));
+    for (field, value) in [
+        ("Encryption", &security.encryption),
+        ("Acknowledgments", &security.acknowledgments),
+        ("Policy", &security.policy),
+        ("Hiring", &security.hiring),
+        ("Preferred-Languages", &security.preferred_languages),
+    ] {
+        if !value.is_empty() {
+            lines.push(format!("{field}: {value}"));
+        }
+    }
+    lines.push(format!(
+        "Canonical: {}/.well-known/security.txt",
+        config.url
+    ));
+
+    let dir = Path::new(outdir).join(".well-known");
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    write(
+        &dir.join("security.txt"),
+        &format!("{}\n", lines.join("\n")),
+    )
+}
+
+// Create the favicons, the icon for Apple devices and the icons of the web app manifest from a single image
+pub fn render_icons(
+    config: &Config,
+    icon: &ConfigIcon,
+    root: &str,
+    outdir: &str,
+) -> Result<(), String> {
+    log::info!("render_icons");
+
+    let path = Path::new(root).join(&icon.file);
+    let source = image::open(&path)
+        .map_err(|err| format!("Could not open the icon {:?}: {err}", path.display()))?;
+    if source.width() != source.height() {
+        log::warn!(
+            "The icon {:?} is not square, it will be cropped",
+            path.display()
+        );
+    }
+    if source.width() < 512 || source.height() < 512 {
+        log::warn!(
+            "The icon {:?} is smaller than 512x512, it will be enlarged",
+            path.display()
+        );
+    }
+
+    let resize = |size| source.resize_to_fill(size, size, FilterType::Lanczos3);
+    resize(32)
+        .save_with_format(Path::new(outdir).join("favicon.ico"), ImageFormat::Ico)
+        .map_err(|err| format!("Could not save favicon.ico: {err}"))?;
+    for (size, name) in ICONS {
+        resize(size)
+            .save_with_format(Path::new(outdir).join(name), ImageFormat::Png)
+            .map_err(|err| format!("Could not save {name}: {err}"))?;
+    }
+
+    let icons = [192, 512].map(|size| {
+        serde_json::json!({
+            "src": format!("/icon-{size}x{size}.png"),
+            "sizes": format!("{size}x{size}"),
+            "type": "image/png",
+        })
+    });
+    let manifest = serde_json::json!({
+        "name": config.site_name,
+        "short_name": if icon.short_name.is_empty() { &config.site_name } else { &icon.short_name },
+        "start_url": "/",
+        "display": "standalone",
+        "theme_color": icon.theme_color,
+        "background_color": icon.background_color,
+        "icons": icons,
+    });
+    let manifest = serde_json::to_string_pretty(&manifest).map_err(|err| err.to_string())?;
+    write(
+        &Path::new(outdir).join("manifest.webmanifest"),
+        &format!("{manifest}\n"),
+    )
+}
+
+fn write(path: &Path, text: &str) -> Result<(), String> {
+    fs::write(path, text).map_err(|err| format!("Could not write {:?}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{read_config, ConfigRobots};
+
+    #[test]
+    fn robots_txt() {
+        let dir = tempdir::TempDir::new("robots").unwrap();
+        let outdir = dir.path().to_str().unwrap();
+        let mut config = read_config("test_cases/demo").unwrap();
+        config.robots = vec![];
+
+        render_robots_txt(&config, outdir).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("robots.txt")).unwrap(),
+            "Sitemap: https://rust.code-maven.com/sitemap.xml\n\nUser-agent: *\n\n"
+        );
+
+        config.robots = vec![
+            ConfigRobots {
+                user_agent: String::from("*"),
+                allow: vec![String::from("/drafts/public")],
+                disallow: vec![String::from("/drafts/")],
+                crawl_delay: None,
+            },
+            ConfigRobots {
+                user_agent: String::from("GPTBot"),
+                allow: vec![],
+                disallow: vec![String::from("/")],
+                crawl_delay: Some(10),
+            },
+        ];
+        render_robots_txt(&config, outdir).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("robots.txt")).unwrap(),
+            "Sitemap: https://rust.code-maven.com/sitemap.xml\n\nUser-agent: *\nDisallow: /drafts/\nAllow: /drafts/public\n\nUser-agent: GPTBot\nDisallow: /\nCrawl-delay: 10\n\n"
+        );
+    }
+
+    #[test]
+    fn icons() {
+        let dir = tempdir::TempDir::new("icons").unwrap();
+        let outdir = dir.path().to_str().unwrap();
+        let config = read_config("test_cases/demo").unwrap();
+        let icon = ConfigIcon {
+            file: String::from("examples/files/code_maven_440x440.png"),
+            short_name: String::from("CM"),
+            theme_color: String::from("#00d1b2"),
+            background_color: String::from("#ffffff"),
+        };
+
+        render_icons(&config, &icon, "test_cases/demo", outdir).unwrap();
+        let favicon = image::open(dir.path().join("favicon.ico")).unwrap();
+        assert_eq!((favicon.width(), favicon.height()), (32, 32));
+        let apple = image::open(dir.path().join("apple-touch-icon.png")).unwrap();
+        assert_eq!((apple.width(), apple.height()), (180, 180));
+        let manifest: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(dir.path().join("manifest.webmanifest")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest["short_name"], "CM");
+        assert_eq!(manifest["icons"][1]["src"], "/icon-512x512.png");
+    }
+}
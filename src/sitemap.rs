@@ -0,0 +1,241 @@
+use std::fs::File;
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::tags;
+use crate::{is_external, Config, Page};
+
+// The limit of the number of URLs in a single sitemap file
+pub const SITEMAP_LIMIT: usize = 50_000;
+
+static IMG_SRC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"<img src="([^"]+)""#).unwrap());
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+struct Entry {
+    loc: String,
+    lastmod: String,
+    images: Vec<String>,
+}
+
+// Save the sitemap.xml. If there are more than `limit` URLs it is a sitemap index
+// listing sitemap-1.xml, sitemap-2.xml, ... each with at most `limit` URLs.
+pub fn render_sitemap(config: &Config, pages: &[Page], outdir: &str, limit: usize) {
+    log::info!("render_sitemap");
+
+    let entries = collect_entries(config, pages);
+    let url = &config.url;
+    let path = Path::new(outdir).join("sitemap.xml");
+
+    if entries.len() <= limit {
+        save(
+            include_str!("../templates/sitemap.xml"),
+            &path,
+            &liquid::object!({ "entries": entries }),
+        );
+        return;
+    }
+
+    let mut sitemaps = vec![];
+    for (index, chunk) in entries.chunks(limit.max(1)).enumerate() {
+        let filename = format!("sitemap-{}.xml", index + 1);
+        save(
+            include_str!("../templates/sitemap.xml"),
+            &Path::new(outdir).join(&filename),
+            &liquid::object!({ "entries": chunk }),
+        );
+        sitemaps.push(liquid::object!({
+            "loc": format!("{url}/{filename}"),
+            "lastmod": chunk.iter().map(|entry| entry.lastmod.clone()).max().unwrap_or_default(),
+        }));
+    }
+    save(
+        include_str!("../templates/sitemap_index.xml"),
+        &path,
+        &liquid::object!({ "sitemaps": sitemaps }),
+    );
+}
+
+fn save(template: &str, path: &Path, globals: &liquid::Object) {
+    let template = liquid::ParserBuilder::with_stdlib()
+        .build()
+        .unwrap()
+        .parse(template)
+        .unwrap();
+    let output = template.render(globals).unwrap();
+
+    let mut file = File::create(path).unwrap();
+    writeln!(&mut file, "{output}").unwrap();
+}
+
+// The published pages, the tag pages and the archive
+fn collect_entries(config: &Config, pages: &[Page]) -> Vec<Entry> {
+    let url = &config.url;
+    let images = config
+        .sitemap
+        .as_ref()
+        .is_some_and(|sitemap| sitemap.images);
+
+    let pages = pages
+        .iter()
+        .filter(|page| page.published)
+        .filter(|page| page.redirect.is_none())
+        .filter(|page| page.sitemap && !page.noindex)
+        .collect::<Vec<_>>();
+
+    let mut entries = pages
+        .iter()
+        .map(|page| Entry {
+            loc: if page.url_path == "index" {
+                format!("{url}/")
+            } else {
+                format!("{url}/{}", page.url_path)
+            },
+            lastmod: date(&page.modified),
+            images: if images {
+                page_images(url, page)
+            } else {
+                vec![]
+            },
+        })
+        .collect::<Vec<_>>();
+
+    let last = pages
+        .iter()
+        .map(|page| page.modified)
+        .max()
+        .unwrap_or_default();
+    if !pages.iter().any(|page| page.url_path == "archive") {
+        entries.push(Entry {
+            loc: format!("{url}/archive"),
+            lastmod: date(&last),
+            images: vec![],
+        });
+    }
+
+    let tags = tags::collect_tags(config, pages.iter().copied());
+    if !tags.is_empty() {
+        entries.push(Entry {
+            loc: format!("{url}/tags/"),
+            lastmod: date(&last),
+            images: vec![],
+        });
+    }
+    entries.extend(tags.into_iter().map(|tag| Entry {
+        loc: format!("{url}/tags/{}", tag.path),
+        lastmod: date(&tag.modified),
+        images: vec![],
+    }));
+
+    entries
+}
+
+fn date(timestamp: &DateTime<FixedOffset>) -> String {
+    timestamp.format("%Y-%m-%d").to_string()
+}
+
+// The local images of the page. They are copied to the same path in the site.
+fn page_images(url: &str, page: &Page) -> Vec<String> {
+    let mut images = vec![];
+    for captures in IMG_SRC.captures_iter(&page.content) {
+        let src = &captures[1];
+        if is_external(src) {
+            continue;
+        }
+        let image = format!("{url}/{}", src.trim_start_matches('/'));
+        if !images.contains(&image) {
+            images.push(image);
+        }
+    }
+    images
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{read_config, ConfigSitemap};
+
+    fn modified(text: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(text).unwrap()
+    }
+
+    #[test]
+    fn entries() {
+        let mut config = read_config("test_cases/demo").unwrap();
+        config.sitemap = Some(ConfigSitemap { images: true });
+        let pages = vec![
+            Page {
+                modified: modified("2024-01-02T10:00:00Z"),
+                content: String::from(
+                    r#"<img src="examples/a.png" alt="" /><img src="https://example.com/b.png" alt="" />"#,
+                ),
+                ..Page::for_test("first", &["Rust", "web"])
+            },
+            Page {
+                modified: modified("2024-01-05T10:00:00Z"),
+                ..Page::for_test("second", &["rust"])
+            },
+            Page {
+                modified: modified("2024-02-01T10:00:00Z"),
+                sitemap: false,
+                ..Page::for_test("hidden", &["secret"])
+            },
+        ];
+
+        let entries = collect_entries(&config, &pages);
+        let locs = entries
+            .iter()
+            .map(|entry| entry.loc.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            locs,
+            vec![
+                "https://rust.code-maven.com/first",
+                "https://rust.code-maven.com/second",
+                "https://rust.code-maven.com/archive",
+                "https://rust.code-maven.com/tags/",
+                // The parent of rust in the config of the demo
+                "https://rust.code-maven.com/tags/programming",
+                "https://rust.code-maven.com/tags/rust",
+                "https://rust.code-maven.com/tags/web",
+            ]
+        );
+        assert_eq!(
+            entries[0].images,
+            vec!["https://rust.code-maven.com/examples/a.png"]
+        );
+        assert_eq!(entries[4].lastmod, "2024-01-05");
+        assert_eq!(entries[5].lastmod, "2024-01-05");
+        assert_eq!(entries[6].lastmod, "2024-01-02");
+    }
+
+    #[test]
+    fn split() {
+        let config = read_config("test_cases/demo").unwrap();
+        let pages = vec![
+            Page {
+                modified: modified("2024-01-02T10:00:00Z"),
+                ..Page::for_test("first", &[])
+            },
+            Page {
+                modified: modified("2024-01-05T10:00:00Z"),
+                ..Page::for_test("second", &[])
+            },
+        ];
+        let dir = tempdir::TempDir::new("sitemap").unwrap();
+        let outdir = dir.path().to_str().unwrap();
+
+        render_sitemap(&config, &pages, outdir, 2);
+        let index = std::fs::read_to_string(dir.path().join("sitemap.xml")).unwrap();
+        assert!(index.contains("<sitemapindex"));
+        assert!(index.contains("<loc>https://rust.code-maven.com/sitemap-2.xml</loc>"));
+        let second = std::fs::read_to_string(dir.path().join("sitemap-2.xml")).unwrap();
+        assert!(second.contains("<loc>https://rust.code-maven.com/archive</loc>"));
+        assert!(!dir.path().join("sitemap-3.xml").exists());
+    }
+}
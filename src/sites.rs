@@ -0,0 +1,290 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::curly::{protect_inline_code, restore_code};
+use crate::fences::fenced_lines;
+use crate::{get_pages_path, read_config, read_pages, Config, ConfigSite, Page};
+
+// [text](site:rust/traits)
+static INLINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\]\(<?)site:([^)\s>]*)").unwrap());
+
+// [traits]: site:rust/traits
+static DEFINITION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^( {0,3}\[[^\]]+\]:[ \t]*<?)site:([^\s>]*)").unwrap());
+
+// The pages.json of a site listing its published pages for the other sites
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub url: String,
+    pub pages: Vec<ManifestPage>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestPage {
+    pub url_path: String,
+    pub title: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Site {
+    pub url: String,
+    pub pages: HashSet<String>,
+}
+
+// The URL and the published pages of each of the sites in the config.
+// Their paths and manifests are relative to the root of this site.
+pub fn read_sites(config: &Config, root: &str) -> Result<HashMap<String, Site>, String> {
+    log::info!("read_sites");
+
+    config
+        .sites
+        .iter()
+        .map(|(name, site)| Ok((name.clone(), read_site(name, site, root)?)))
+        .collect()
+}
+
+fn read_site(name: &str, site: &ConfigSite, root: &str) -> Result<Site, String> {
+    if !site.manifest.is_empty() {
+        let manifest_path = Path::new(root).join(&site.manifest);
+        let content = fs::read_to_string(manifest_path).map_err(|err| {
+            format!(
+                "Could not read the manifest '{}' of the site '{name}': {err}",
+                site.manifest
+            )
+        })?;
+        let manifest: Manifest = serde_json::from_str(&content).map_err(|err| {
+            format!(
+                "Invalid manifest '{}' of the site '{name}': {err}",
+                site.manifest
+            )
+        })?;
+        return Ok(Site {
+            url: manifest.url,
+            pages: manifest
+                .pages
+                .into_iter()
+                .map(|page| page.url_path)
+                .collect(),
+        });
+    }
+
+    let site_root = Path::new(root).join(&site.path).display().to_string();
+    let config = read_config(&site_root)
+        .map_err(|err| format!("Could not read the config of the site '{name}': {err}"))?;
+    let pages = read_pages(&config, &get_pages_path(&site_root, ""), &site_root);
+    Ok(Site {
+        url: config.url,
+        pages: pages
+            .into_iter()
+            .filter(|page| page.published)
+            .map(|page| page.url_path)
+            .collect(),
+    })
+}
+
+// Replace the site:name/path links and the site:name/path in the `original` field by the URL of the page
+// in the other site. Links to sites that are not in the config or to pages that don't exist are errors.
+pub fn resolve_site_links<S: core::hash::BuildHasher>(
+    sites: &HashMap<String, Site, S>,
+    pages: Vec<Page>,
+    math: bool,
+) -> Result<Vec<Page>, String> {
+    log::info!("resolve_site_links");
+
+    pages
+        .into_iter()
+        .map(|mut page| {
+            if let Some(link) = page.original.strip_prefix("site:") {
+                page.original = resolve(sites, link)
+                    .map_err(|err| format!("{err} in the front matter of '{}'", page.filename))?;
+            }
+            if !page.content.contains("site:") {
+                return Ok(page);
+            }
+
+            let fenced = fenced_lines(&page.content, math);
+            let mut rows = vec![];
+            for (index, row) in page.content.split('\n').enumerate() {
+                if fenced[index] {
+                    rows.push(row.to_owned());
+                    continue;
+                }
+                let row = resolve_row(sites, row).map_err(|err| {
+                    format!(
+                        "{err} in '{}' line {}",
+                        page.filename,
+                        page.content_line + index
+                    )
+                })?;
+                rows.push(row);
+            }
+            page.content = rows.join("\n");
+            Ok(page)
+        })
+        .collect()
+}
+
+fn resolve_row<S: core::hash::BuildHasher>(
+    sites: &HashMap<String, Site, S>,
+    row: &str,
+) -> Result<String, String> {
+    let mut code = vec![];
+    let text = protect_inline_code(row, &mut code);
+
+    let mut error = None;
+    let mut replace = |captures: &Captures| match resolve(sites, &captures[2]) {
+        Ok(url) => format!("{}{url}", &captures[1]),
+        Err(err) => {
+            error.get_or_insert(err);
+            captures[0].to_owned()
+        }
+    };
+    let text = INLINE.replace_all(&text, &mut replace).into_owned();
+    let text = DEFINITION.replace_all(&text, &mut replace).into_owned();
+
+    match error {
+        Some(err) => Err(err),
+        None => Ok(restore_code(&text, &code)),
+    }
+}
+
+// rust/traits#bounds -> https://rust.code-maven.com/traits#bounds
+fn resolve<S: core::hash::BuildHasher>(
+    sites: &HashMap<String, Site, S>,
+    link: &str,
+) -> Result<String, String> {
+    let (name, path) = link.split_once('/').unwrap_or((link, ""));
+    let site = sites
+        .get(name)
+        .ok_or_else(|| format!("Unknown site '{name}' in 'site:{link}'"))?;
+    let page = path.split(['#', '?']).next().unwrap_or_default();
+    if !site.pages.contains(page) {
+        return Err(format!(
+            "The page '{page}' of 'site:{link}' does not exist in the site '{name}'"
+        ));
+    }
+    Ok(format!("{}/{path}", site.url))
+}
+
+// Save the pages.json other sites can use to link to the pages of this site
+pub fn render_manifest(config: &Config, pages: &[Page], outdir: &str) -> Result<(), String> {
+    log::info!("render_manifest");
+
+    let mut manifest_pages = pages
+        .iter()
+        .filter(|page| page.published)
+        .map(|page| ManifestPage {
+            url_path: page.url_path.clone(),
+            title: page.title.clone(),
+        })
+        .collect::<Vec<_>>();
+    manifest_pages.sort_by(|first, second| first.url_path.cmp(&second.url_path));
+    let manifest = Manifest {
+        url: config.url.clone(),
+        pages: manifest_pages,
+    };
+
+    let manifest = serde_json::to_string_pretty(&manifest).map_err(|err| err.to_string())?;
+    let path = Path::new(outdir).join("pages.json");
+    fs::write(&path, format!("{manifest}\n"))
+        .map_err(|err| format!("Could not write {:?}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sites() -> HashMap<String, Site> {
+        HashMap::from([(
+            String::from("rust"),
+            Site {
+                url: String::from("https://rust.code-maven.com"),
+                pages: HashSet::from([String::new(), String::from("traits")]),
+            },
+        )])
+    }
+
+    #[test]
+    fn links() {
+        let page = Page {
+            filename: String::from("links.md"),
+            content_line: 5,
+            original: String::from("site:rust/traits"),
+            content: String::from(
+                "See [traits](site:rust/traits#bounds), [Rust](<site:rust/>) and [the traits][t].\n`[code](site:rust/none)`\n```\n[block](site:rust/none)\n```\n\n[t]: site:rust/traits",
+            ),
+            ..Page::default()
+        };
+        let pages = resolve_site_links(&sites(), vec![page], false).unwrap();
+        assert_eq!(pages[0].original, "https://rust.code-maven.com/traits");
+        assert_eq!(
+            pages[0].content,
+            "See [traits](https://rust.code-maven.com/traits#bounds), [Rust](<https://rust.code-maven.com/>) and [the traits][t].\n`[code](site:rust/none)`\n```\n[block](site:rust/none)\n```\n\n[t]: https://rust.code-maven.com/traits"
+        );
+    }
+
+    #[test]
+    fn dangling() {
+        let page = |content: &str| Page {
+            filename: String::from("links.md"),
+            content_line: 5,
+            content: content.to_owned(),
+            ..Page::default()
+        };
+        assert_eq!(
+            resolve_site_links(&sites(), vec![page("\n[traits](site:rust/trait)")], false)
+                .err()
+                .unwrap(),
+            "The page 'trait' of 'site:rust/trait' does not exist in the site 'rust' in 'links.md' line 6"
+        );
+        assert_eq!(
+            resolve_site_links(&sites(), vec![page("[list](site:perl/list)")], false)
+                .err()
+                .unwrap(),
+            "Unknown site 'perl' in 'site:perl/list' in 'links.md' line 5"
+        );
+    }
+
+    #[test]
+    fn sibling_from_path_and_manifest() {
+        let dir = tempdir::TempDir::new("sites").unwrap();
+        let outdir = dir.path().to_str().unwrap();
+        let config = read_config("test_cases/demo").unwrap();
+        let pages = read_pages(
+            &config,
+            &get_pages_path("test_cases/demo", ""),
+            "test_cases/demo",
+        );
+        render_manifest(&config, &pages, outdir).unwrap();
+
+        let from_path = read_site(
+            "demo",
+            &ConfigSite {
+                path: String::from("test_cases/demo"),
+                manifest: String::new(),
+            },
+            ".",
+        )
+        .unwrap();
+        let from_manifest = read_site(
+            "demo",
+            &ConfigSite {
+                path: String::new(),
+                manifest: dir.path().join("pages.json").display().to_string(),
+            },
+            ".",
+        )
+        .unwrap();
+        assert_eq!(from_path, from_manifest);
+        assert_eq!(from_path.url, "https://rust.code-maven.com");
+        assert!(from_path.pages.contains("sigils"));
+        assert!(!from_path.pages.contains("draft"));
+    }
+}
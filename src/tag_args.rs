@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use liquid_core::parser::{TagToken, TryMatchToken};
+use liquid_core::Result;
+use liquid_core::TagTokenIter;
+use liquid_core::ValueView as _;
+
+// The `key=value` pairs of a tag. The keys listed in `flags` can also be used without a value
+// and then they are set to "true".
+pub fn parse_options(
+    arguments: TagTokenIter<'_>,
+    tag: &str,
+    flags: &[&str],
+) -> Result<Vec<(String, String)>> {
+    let mut tokens = arguments.collect::<Vec<_>>().into_iter().peekable();
+    let mut options = vec![];
+
+    while let Some(key) = tokens.next() {
+        let key = key.as_str().to_owned();
+
+        if tokens.peek().map(TagToken::as_str) != Some("=") {
+            if flags.contains(&key.as_str()) {
+                options.push((key, String::from("true")));
+                continue;
+            }
+            return Err(liquid_core::error::Error::with_msg(format!(
+                "Assignment operator \"=\" expected after '{key}' in {tag} tag."
+            )));
+        }
+        tokens.next();
+
+        let token = tokens
+            .next()
+            .ok_or_else(|| liquid_core::error::Error::with_msg("Identifier or value expected"))?;
+        let value = match token.expect_literal() {
+            TryMatchToken::Matches(value) => value.to_kstr().into_string(),
+            TryMatchToken::Fails(value) => return value.raise_error().into_err(),
+        };
+        options.push((key, value));
+    }
+
+    Ok(options)
+}
+
+pub fn parse_bool(tag: &str, key: &str, value: &str) -> Result<bool> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(liquid_core::error::Error::with_msg(format!(
+            "Invalid value '{value}' for '{key}' in {tag} tag. Expected true or false"
+        ))),
+    }
+}
+
+// The options of a tag that are all given by name, e.g. `{% vimeo id="123" start=30 %}`
+pub fn parse_named_options(
+    arguments: TagTokenIter<'_>,
+    tag: &str,
+    required: &[&str],
+    optional: &[&str],
+) -> Result<HashMap<String, String>> {
+    let mut options = HashMap::new();
+    for (key, value) in parse_options(arguments, tag, &[])? {
+        if !required.contains(&key.as_str()) && !optional.contains(&key.as_str()) {
+            return Err(liquid_core::error::Error::with_msg(format!(
+                "Unknown option '{key}' in {tag} tag. Expected one of: {}",
+                [required, optional].concat().join(", ")
+            )));
+        }
+        if options.insert(key.clone(), value).is_some() {
+            return Err(liquid_core::error::Error::with_msg(format!(
+                "Option '{key}' given more than once in {tag} tag."
+            )));
+        }
+    }
+
+    for key in required {
+        if !options.contains_key(*key) {
+            return Err(liquid_core::error::Error::with_msg(format!(
+                "\"{key}\" expected in {tag} tag."
+            )));
+        }
+    }
+
+    Ok(options)
+}
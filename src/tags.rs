@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
+use crate::{topath, Config, Page};
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct TagLink {
+    pub name: String,
+    pub path: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    pub path: String,
+    pub title: String,
+    pub description: String,
+    pub text: String,
+
+    // The number of published pages with this tag or with one of its descendants
+    pub count: usize,
+    pub modified: DateTime<FixedOffset>,
+
+    pub parent: Option<TagLink>,
+    pub children: Vec<TagLink>,
+}
+
+// The tag and its parent, grandparent, ... as listed in the tags of the config.
+pub fn ancestors(config: &Config, tag: &str) -> Vec<String> {
+    let mut tags = vec![];
+    let mut current = tag.to_lowercase();
+    // The check for repeated tags stops loops in configs that were not read by read_config
+    while !tags.contains(&current) {
+        tags.push(current.clone());
+        match config
+            .tags
+            .details
+            .iter()
+            .find(|details| details.name == current && !details.parent.is_empty())
+        {
+            Some(details) => current.clone_from(&details.parent),
+            None => break,
+        }
+    }
+    tags
+}
+
+// Does the page have the tag or one of its descendants?
+pub fn has_tag(config: &Config, page: &Page, tag: &str) -> bool {
+    page.tags
+        .iter()
+        .any(|page_tag| ancestors(config, page_tag).iter().any(|name| name == tag))
+}
+
+// The tags of the published pages and their ancestors, sorted by name
+pub fn collect_tags<'page, P: IntoIterator<Item = &'page Page>>(
+    config: &Config,
+    pages: P,
+) -> Vec<Tag> {
+    log::info!("collect_tags");
+
+    let mut counts: HashMap<String, (usize, DateTime<FixedOffset>)> = HashMap::new();
+    for page in pages {
+        if page.redirect.is_some() || !page.published {
+            continue;
+        }
+        let mut tags = page
+            .tags
+            .iter()
+            .flat_map(|tag| ancestors(config, tag))
+            .collect::<Vec<_>>();
+        tags.sort();
+        tags.dedup();
+        for tag in tags {
+            let (count, modified) = counts.entry(tag).or_insert((0, page.modified));
+            *count += 1;
+            *modified = (*modified).max(page.modified);
+        }
+    }
+
+    let link = |name: &str| TagLink {
+        name: name.to_owned(),
+        path: topath(name),
+        count: counts.get(name).map_or(0, |(count, _modified)| *count),
+    };
+
+    let mut names = counts.keys().collect::<Vec<_>>();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| {
+            let (count, modified) = counts[name];
+            let tag_details = config
+                .tags
+                .details
+                .iter()
+                .find(|details| &details.name == name);
+            let text = |field: fn(&crate::TagDetails) -> &String| {
+                tag_details
+                    .map(field)
+                    .filter(|value| !value.is_empty())
+                    .cloned()
+            };
+            Tag {
+                name: name.clone(),
+                path: topath(name),
+                title: text(|details| &details.title)
+                    .unwrap_or_else(|| format!("Articles tagged with '{name}'")),
+                description: text(|details| &details.description)
+                    .unwrap_or_else(|| format!("Articles tagged with '{name}'")),
+                text: text(|details| &details.text).unwrap_or_default(),
+                count,
+                modified,
+                parent: text(|details| &details.parent).map(|parent| link(&parent)),
+                children: config
+                    .tags
+                    .details
+                    .iter()
+                    .filter(|child| &child.parent == name && counts.contains_key(&child.name))
+                    .map(|child| link(&child.name))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::{read_config, TagDetails};
+
+    fn details(name: &str, parent: &str) -> TagDetails {
+        TagDetails {
+            name: name.to_owned(),
+            title: String::new(),
+            description: String::new(),
+            aliases: vec![],
+            parent: parent.to_owned(),
+            text: String::new(),
+        }
+    }
+
+    #[test]
+    fn hierarchy() {
+        let mut config = read_config("test_cases/demo").unwrap();
+        config.tags.details = vec![
+            details("programming", ""),
+            TagDetails {
+                title: String::from("Rust"),
+                ..details("rust", "programming")
+            },
+            details("python", "programming"),
+        ];
+        let pages = vec![
+            Page::for_test("a", &["Rust", "web"]),
+            Page::for_test("b", &["rust", "programming"]),
+            Page {
+                published: false,
+                ..Page::for_test("c", &["python"])
+            },
+        ];
+
+        let tags = collect_tags(&config, &pages);
+        let names = tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["programming", "rust", "web"]);
+
+        assert_eq!(tags[0].count, 2);
+        assert_eq!(
+            tags[0].children,
+            vec![TagLink {
+                name: String::from("rust"),
+                path: String::from("rust"),
+                count: 2
+            }]
+        );
+        assert_eq!(tags[1].title, "Rust");
+        assert_eq!(tags[1].parent.as_ref().unwrap().name, "programming");
+        assert_eq!(tags[2].title, "Articles tagged with 'web'");
+        assert!(has_tag(&config, &pages[0], "programming"));
+        assert!(!has_tag(&config, &pages[0], "python"));
+    }
+
+    #[test]
+    fn loop_in_parents() {
+        let mut config = read_config("test_cases/demo").unwrap();
+        config.tags.details = vec![details("a", "b"), details("b", "a")];
+        assert_eq!(ancestors(&config, "A"), vec!["a", "b"]);
+    }
+}
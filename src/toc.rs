@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::ConfigToc;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Heading {
+    pub level: u8,
+    pub id: String,
+    pub title: String,
+    pub children: Vec<Heading>,
+}
+
+// Add an id and a link to itself to every heading in the HTML generated from the markdown
+// and return the headings between the min and max levels nested in each other.
+pub fn add_heading_ids(html: &str, config: Option<&ConfigToc>) -> (String, Vec<Heading>) {
+    let re = Regex::new(r"<h([1-6])>(.*?)</h[1-6]>").unwrap();
+    let re_tag = Regex::new(r"<[^>]+>").unwrap();
+
+    let mut seen: HashMap<String, u32> = HashMap::new();
+    let mut headings = vec![];
+
+    let html = re.replace_all(html, |capture: &regex::Captures| {
+        let level = capture[1].parse::<u8>().unwrap();
+        let inner = &capture[2];
+        let title = re_tag.replace_all(inner, "").trim().to_owned();
+
+        let mut id = slugify(&title);
+        let count = seen.entry(id.clone()).or_insert(0);
+        if *count > 0 {
+            id = format!("{id}-{count}");
+        }
+        *count += 1;
+
+        if let Some(config) = config {
+            if config.min_level <= level && level <= config.max_level {
+                headings.push(Heading {
+                    level,
+                    id: id.clone(),
+                    title,
+                    children: vec![],
+                });
+            }
+        }
+
+        let class = match level {
+            1 => r#" class="title""#,
+            2 => r#" class="title is-4""#,
+            3 => r#" class="title is-5""#,
+            _ => "",
+        };
+        format!(
+            r##"<h{level}{class} id="{id}">{inner} <a class="anchor" href="#{id}" aria-label="Link to this section">#</a></h{level}>"##
+        )
+    });
+
+    (html.into_owned(), nest_headings(headings))
+}
+
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for chr in text.to_lowercase().chars() {
+        if chr.is_alphanumeric() {
+            slug.push(chr);
+            continue;
+        }
+        if (chr.is_whitespace() || chr == '-' || chr == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+
+    if slug.is_empty() {
+        return String::from("section");
+    }
+    slug.to_owned()
+}
+
+fn nest_headings(headings: Vec<Heading>) -> Vec<Heading> {
+    let mut nested = vec![];
+    for heading in headings {
+        insert_heading(&mut nested, heading);
+    }
+    nested
+}
+
+fn insert_heading(headings: &mut Vec<Heading>, heading: Heading) {
+    match headings.last_mut() {
+        Some(last) if last.level < heading.level => insert_heading(&mut last.children, heading),
+        _ => headings.push(heading),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Hello World"), "hello-world");
+        assert_eq!(slugify("  What is `println!`? "), "what-is-println");
+        assert_eq!(slugify("snake_case -- and more"), "snake-case-and-more");
+        assert_eq!(slugify("???"), "section");
+    }
+
+    #[test]
+    fn ids_and_toc() {
+        let config = ConfigToc {
+            min_level: 2,
+            max_level: 3,
+            title: String::from("Contents"),
+        };
+        let html = "<h1>Title</h1>\n<h2>Intro</h2>\n<h3>Some <code>code</code></h3>\n<h4>Deep</h4>\n<h2>Intro</h2>\n";
+        let (html, headings) = add_heading_ids(html, Some(&config));
+
+        assert_eq!(
+            html,
+            r##"<h1 class="title" id="title">Title <a class="anchor" href="#title" aria-label="Link to this section">#</a></h1>
+<h2 class="title is-4" id="intro">Intro <a class="anchor" href="#intro" aria-label="Link to this section">#</a></h2>
+<h3 class="title is-5" id="some-code">Some <code>code</code> <a class="anchor" href="#some-code" aria-label="Link to this section">#</a></h3>
+<h4 id="deep">Deep <a class="anchor" href="#deep" aria-label="Link to this section">#</a></h4>
+<h2 class="title is-4" id="intro-1">Intro <a class="anchor" href="#intro-1" aria-label="Link to this section">#</a></h2>
+"##
+        );
+
+        assert_eq!(
+            headings,
+            vec![
+                Heading {
+                    level: 2,
+                    id: String::from("intro"),
+                    title: String::from("Intro"),
+                    children: vec![Heading {
+                        level: 3,
+                        id: String::from("some-code"),
+                        title: String::from("Some code"),
+                        children: vec![],
+                    }],
+                },
+                Heading {
+                    level: 2,
+                    id: String::from("intro-1"),
+                    title: String::from("Intro"),
+                    children: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn deep_toc() {
+        let config = ConfigToc {
+            min_level: 1,
+            max_level: 6,
+            title: String::from("Contents"),
+        };
+        let html = "<h1>A</h1><h2>B</h2><h3>C</h3><h4>D</h4><h5>E</h5><h6>F</h6><h2>G</h2>";
+        let (_html, headings) = add_heading_ids(html, Some(&config));
+
+        assert_eq!(headings.len(), 1);
+        let mut heading = &headings[0];
+        for id in ["b", "c", "d", "e", "f"] {
+            assert_eq!(heading.children[0].id, id);
+            heading = &heading.children[0];
+        }
+        assert_eq!(headings[0].children[1].id, "g");
+    }
+
+    #[test]
+    fn no_toc() {
+        let (_html, headings) = add_heading_ids("<h2>Intro</h2>", None);
+        assert!(headings.is_empty());
+    }
+}
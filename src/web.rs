@@ -1,6 +1,5 @@
 #![allow(clippy::std_instead_of_core)]
 
-use std::collections::HashMap;
 use std::error::Error;
 use std::fs;
 use std::fs::File;
@@ -11,15 +10,25 @@ use std::path::PathBuf;
 use feed_rs::parser;
 
 use crate::{
-    collect_backlinks, copy_files, filter_words, get_files_to_copy, get_pages_path, markdown_pages,
-    read_config, read_config_file, read_pages, topath, Author, Config, Page, ToPath,
+    copy_dir, copy_files, filter_words, get_files_to_copy, get_pages_path, is_external,
+    markdown_pages, read_config, read_config_file, read_pages, Author, Config, Page, ToPath,
 };
 
+use crate::backlinks;
+use crate::banner;
 use crate::curly::{check_for_invalid_curly_code, process_curly_tags};
+use crate::highlight::render_css;
+use crate::images;
+use crate::redirects::{collect_redirects, render_redirects};
+use crate::related;
+use crate::seo;
+use crate::site_files;
+use crate::sitemap;
+use crate::sites;
+use crate::tags;
 
 pub type Partials = liquid::partials::EagerCompiler<liquid::partials::InMemorySource>;
 
-type Tags = HashMap<String, i32>;
 const IMG: &str = "img";
 
 pub fn web(root: &str, config_path: &str, path_to_pages: &str, outdir: &str) -> Result<(), String> {
@@ -50,87 +59,81 @@ pub fn web(root: &str, config_path: &str, path_to_pages: &str, outdir: &str) ->
     let pages_path = get_pages_path(root, path_to_pages);
 
     let pages = read_pages(&config, &pages_path, root);
-    let pages = collect_backlinks(pages);
-    let paths = get_files_to_copy(&pages).unwrap();
-    let pages = process_curly_tags(&config, root, pages);
-    check_for_invalid_curly_code(&pages);
-    let pages = markdown_pages(pages);
-
-    let tags: Tags = collect_tags(&pages);
-    copy_files(root, outdir, &paths);
-    copy_files(
-        root,
-        outdir,
-        &config
-            .authors
-            .iter()
-            .map(|author| PathBuf::from("images").join(author.picture.clone()))
-            .filter(|path| path.exists())
-            .collect::<Vec<PathBuf>>(),
-    );
-
-    render_pages(&config, &pages, outdir, url);
-    render_tag_pages(&config, &pages, &tags, outdir, url);
-    render_sitemap(&pages, &format!("{outdir}/sitemap.xml"), url);
-    render_atom(&config, &pages, &format!("{outdir}/atom.xml"), url)?;
-    render_archive(&config, &pages, outdir, url);
-    render_robots_txt(&format!("{outdir}/robots.txt"), url);
-
-    Ok(())
-}
-
-fn collect_tags(pages: &Vec<Page>) -> Tags {
-    log::info!("collect_tags");
-
-    let mut tags: Tags = HashMap::new();
-    for page in pages {
-        if page.redirect.is_some() {
-            continue;
-        }
-        if !page.published {
-            continue;
-        }
-        for tag in &page.tags {
-            tags.insert(tag.to_lowercase(), 1);
+    let sites = sites::read_sites(&config, root)?;
+    let pages = sites::resolve_site_links(&sites, pages, config.math)?;
+    let pages = backlinks::collect_backlinks(&config, pages);
+    let pages = match &config.related {
+        Some(related_config) => related::collect_related(related_config, pages),
+        None => pages,
+    };
+    let paths = get_files_to_copy(&config, root, &pages).map_err(|err| err.to_string())?;
+    let pages = process_curly_tags(&config, root, outdir, pages);
+    check_for_invalid_curly_code(&config, &pages);
+    let mut pages = markdown_pages(&config, pages)?;
+    if let Some(images_config) = &config.images {
+        let images = images::process_images(images_config, root, outdir, &paths)?;
+        for page in &mut pages {
+            page.content = images::add_srcset(&page.content, images_config, &images);
         }
     }
-    tags
-}
 
-fn render_robots_txt(path: &str, url: &str) {
-    log::info!("render_robots_txt");
+    copy_files(root, outdir, &paths)?;
+    copy_files(root, outdir, &author_pictures(&config, root)?)?;
 
-    let text = format!("Sitemap: {url}/sitemap.xml\n\nUser-agent: *\n");
+    // The files in static/ go to the root of the site, assets/ is copied as it is
+    let static_dir = Path::new(root).join("static");
+    if static_dir.is_dir() {
+        copy_dir(&static_dir, Path::new(outdir))?;
+    }
+    let assets_dir = Path::new(root).join("assets");
+    if assets_dir.is_dir() {
+        copy_dir(&assets_dir, &Path::new(outdir).join("assets"))?;
+    }
 
-    let mut file = File::create(path).unwrap();
-    writeln!(&mut file, "{text}").unwrap();
-}
+    if let Some(highlight) = &config.highlight {
+        render_css(highlight, outdir)?;
+    }
 
-fn render_sitemap(pages: &[Page], path: &str, url: &str) {
-    log::info!("render_sitemap");
+    render_pages(&config, &pages, root, outdir, url);
+    render_redirects(&config, &collect_redirects(&pages), outdir, url);
+    render_tag_pages(&config, &pages, outdir, url);
+    sitemap::render_sitemap(&config, &pages, outdir, sitemap::SITEMAP_LIMIT);
+    render_atom(&config, &pages, &format!("{outdir}/atom.xml"), url)?;
+    render_archive(&config, &pages, root, outdir, url);
+    site_files::render_robots_txt(&config, outdir)?;
+    if config.humans {
+        site_files::render_humans_txt(&config, &pages, outdir)?;
+    }
+    if let Some(security) = &config.security {
+        site_files::render_security_txt(&config, security, outdir)?;
+    }
+    if let Some(icon) = &config.icon {
+        site_files::render_icons(&config, icon, root, outdir)?;
+    }
+    if config.pages_json {
+        sites::render_manifest(&config, &pages, outdir)?;
+    }
 
-    let template = include_str!("../templates/sitemap.xml");
-    let template = liquid::ParserBuilder::with_stdlib()
-        .filter(ToPath)
-        .build()
-        .unwrap()
-        .parse(template)
-        .unwrap();
+    Ok(())
+}
 
-    let pages: Vec<&Page> = pages
+// The pictures of the authors are in the images/ directory
+fn author_pictures(config: &Config, root: &str) -> Result<Vec<PathBuf>, String> {
+    config
+        .authors
         .iter()
-        .filter(|page| page.published)
-        .filter(|page| page.redirect.is_none())
-        .collect();
-
-    let globals = liquid::object!({
-        "pages": &pages,
-        "url": url,
-    });
-    let output = template.render(&globals).unwrap();
-
-    let mut file = File::create(path).unwrap();
-    writeln!(&mut file, "{output}").unwrap();
+        .map(|author| {
+            let path = PathBuf::from("images").join(&author.picture);
+            if !Path::new(root).join(&path).is_file() {
+                return Err(format!(
+                    "The picture '{}' of the author '{}' does not exist",
+                    path.display(),
+                    author.nickname
+                ));
+            }
+            Ok(path)
+        })
+        .collect()
 }
 
 fn render_atom(config: &Config, pages: &[Page], path: &str, url: &str) -> Result<(), String> {
@@ -168,7 +171,7 @@ fn render_atom(config: &Config, pages: &[Page], path: &str, url: &str) -> Result
         "url": url,
         "site_name": config.site_name,
         "author_name": config.author_name,
-        "updated": pages[0].timestamp,
+        "updated": pages.iter().map(|page| page.modified).max().unwrap_or_default(),
     });
     let output = template.render(&globals).unwrap();
 
@@ -184,7 +187,7 @@ fn render_atom(config: &Config, pages: &[Page], path: &str, url: &str) -> Result
     Ok(())
 }
 
-fn render_archive(config: &Config, pages: &[Page], outdir: &str, url: &str) {
+fn render_archive(config: &Config, pages: &[Page], root: &str, outdir: &str, url: &str) {
     log::info!("render_archive");
 
     let partials = match load_templates() {
@@ -225,69 +228,42 @@ fn render_archive(config: &Config, pages: &[Page], outdir: &str, url: &str) {
     writeln!(&mut file, "{output}").unwrap();
 
     let image_file = PathBuf::from(outdir).join(IMG).join("archive.png");
-
-    let banner = banner_builder::Banner {
-        width: 1000,
-        height: 500,
-        text: config.archive.title.clone(),
-        background_color: "FFFFFF".to_owned(),
-        embed: vec![],
-        lines: vec![],
-        size: 24,
-    };
-
-    // Currently we don't embed any images in the banner
-    // so we can use any path.
-    let root = Path::new(".");
-    banner_builder::draw_image(&banner, root, &image_file);
+    if let Err(err) = banner::draw_banner(config, root, &config.archive.title, None, &image_file) {
+        log::error!("{err}");
+        std::process::exit(1);
+    }
 }
 
-fn render_tag_pages(config: &Config, pages: &Vec<Page>, tags: &Tags, outdir: &str, url: &str) {
+fn render_tag_pages(config: &Config, pages: &Vec<Page>, outdir: &str, url: &str) {
     log::info!("render_tag_pages");
 
-    #[expect(clippy::iter_over_hash_type)]
-    for tag in tags.keys() {
-        if tag == ".." {
-            log::error!("We cannot save a file for a tag of 2 dots: '..'");
-            continue;
-        }
-        if tag.contains('/') && tag != "/" {
-            log::error!("For now we don't save tags with / in them:: '{tag}'");
-            continue;
-        }
-        // if !tag.chars().all(char::is_alphanumeric) {
-        //     log::error!("For now we don't save tags with non alphanumeric: '{tag}'");
-        //     continue;
-        // }
-        let mut pages_with_tag: Vec<Page> = vec![];
-        for page in pages {
-            for xtag in &page.tags {
-                if &xtag.to_lowercase() == tag {
-                    pages_with_tag.push(page.clone());
-                }
-            }
-        }
+    let tags = tags::collect_tags(config, pages);
+    for tag in &tags {
+        let pages_with_tag = pages
+            .iter()
+            .filter(|page| {
+                page.published && page.redirect.is_none() && tags::has_tag(config, page, &tag.name)
+            })
+            .collect::<Vec<_>>();
 
         let globals = liquid::object!({
-            "title": format!("Articles tagged with '{}'", tag),
-            "description": format!("Articles about Rust tagged with '{}'", tag),
+            "title": tag.title,
+            "description": tag.description,
             "keywords": vec![""], // TODO: include tag, but make sure we only put there letters and numbers
+            "tag": tag,
             "pages": pages_with_tag,
             "config": config,
             "url": url,
-            "pagepath": format!("tags/{}", topath(tag)),
+            "pagepath": format!("tags/{}", tag.path),
             "site_name": config.site_name,
         });
 
-        let path = Path::new(outdir).join("tags").join(topath(tag));
-        log::info!("render_tag {tag}");
+        let path = Path::new(outdir).join("tags").join(&tag.path);
+        log::info!("render_tag {}", tag.name);
 
         render_any(include_str!("../templates/tag.html"), path, globals);
     }
 
-    let mut tags: Vec<_> = tags.keys().collect();
-    tags.sort();
-
     let globals = liquid::object!({
         "title": config.tags.title,
         "description": config.tags.description,
@@ -330,7 +306,7 @@ fn render_any(template: &str, mut path: PathBuf, globals: liquid::Object) {
     writeln!(&mut file, "{output}").unwrap();
 }
 
-fn render_pages(config: &Config, pages: &Vec<Page>, outdir: &str, url: &str) {
+fn render_pages(config: &Config, pages: &Vec<Page>, root: &str, outdir: &str, url: &str) {
     log::info!("render_pages");
 
     for page in pages {
@@ -338,16 +314,24 @@ fn render_pages(config: &Config, pages: &Vec<Page>, outdir: &str, url: &str) {
             continue;
         }
 
-        let mut outfile = PathBuf::from(&page.filename);
-        outfile.set_extension("html");
         if page.redirect.is_none() {
-            render_and_save_single_page(config, page, outfile, outdir, url);
-        } else {
-            render_and_save_redirect_page(page, outfile, outdir);
+            let outfile = get_output_file(&page.url_path);
+            render_and_save_single_page(config, page, outfile, root, outdir, url);
         }
     }
 }
 
+// A URL path ending with / is saved as an index.html file in the directory.
+pub fn get_output_file(url_path: &str) -> PathBuf {
+    if url_path.is_empty() {
+        return PathBuf::from("index.html");
+    }
+    if url_path.ends_with('/') {
+        return PathBuf::from(url_path).join("index.html");
+    }
+    PathBuf::from(format!("{url_path}.html"))
+}
+
 pub fn load_templates() -> Result<Partials, Box<dyn Error>> {
     // log::info!("load_templates");
 
@@ -368,6 +352,10 @@ pub fn load_templates() -> Result<Partials, Box<dyn Error>> {
         "templates/incl/google.html",
         include_str!("../templates/incl/google.html"),
     );
+    partials.add(
+        "templates/incl/toc.html",
+        include_str!("../templates/incl/toc.html"),
+    );
     Ok(partials)
 }
 
@@ -375,38 +363,37 @@ pub fn render_and_save_single_page(
     config: &Config,
     page: &Page,
     outfile: PathBuf,
+    root: &str,
     outdir: &str,
     url: &str,
 ) {
     let path = Path::new(outdir).join(outfile);
 
     log::info!("render path {}", path.display());
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
 
-    // let image_file = image_file.join(IMG);
-    // let mut image_file = image_file.join(&page.filename);
-    // image_file.set_extension("png");
-    let mut image_path = PathBuf::from(IMG).join(&page.filename);
-    image_path.set_extension("png");
-    let image_file = PathBuf::from(outdir).join(&image_path);
-    // log::warn!("{} {:?}", page.filename, image_file);
-    // log::warn!("{}", page.title);
-    let banner = banner_builder::Banner {
-        width: 1000,
-        height: 500,
-        text: page.title.clone(),
-        background_color: "FFFFFF".to_owned(),
-        embed: vec![],
-        lines: vec![],
-        size: 24,
+    // The image given in the front matter is used instead of the banner
+    let image = if let Some(image) = &page.image {
+        Some(if is_external(image) {
+            image.clone()
+        } else {
+            format!("{url}/{}", image.trim_start_matches('/'))
+        })
+    } else {
+        let mut image_path = PathBuf::from(IMG).join(&page.filename);
+        image_path.set_extension("png");
+        let image_file = PathBuf::from(outdir).join(&image_path);
+        match banner::draw_banner(config, root, &page.title, Some(page), &image_file) {
+            Ok(true) => Some(format!("{url}/{}", image_path.display())),
+            Ok(false) => None,
+            Err(err) => {
+                log::error!("{err} in '{}'", page.filename);
+                std::process::exit(1);
+            }
+        }
     };
 
-    // Currently we don't embed any images in the banner
-    // so we can use any path.
-    let root = Path::new(".");
-
-    let image = banner_builder::draw_image(&banner, root, &image_file);
-
-    match render_single_page(config, page, url, image, image_path) {
+    match render_single_page(config, page, url, image) {
         Ok(output) => {
             let mut file = File::create(path).unwrap();
             writeln!(&mut file, "{output}").unwrap();
@@ -418,23 +405,11 @@ pub fn render_and_save_single_page(
     }
 }
 
-fn render_and_save_redirect_page(page: &Page, outfile: PathBuf, outdir: &str) {
-    let path = Path::new(outdir).join(outfile);
-    log::info!("render redirect page path {}", path.display());
-    let output = format!(
-        r#"<meta http-equiv="refresh" content="0; url={}" />"#,
-        page.redirect.as_ref().unwrap()
-    );
-
-    writeln!(File::create(path).unwrap(), "{output}").unwrap();
-}
-
 fn render_single_page(
     config: &Config,
     page: &Page,
     url: &str,
-    image: bool,
-    image_path: PathBuf,
+    image: Option<String>,
 ) -> Result<String, String> {
     let partials = match load_templates() {
         Ok(partials) => partials,
@@ -466,6 +441,7 @@ fn render_single_page(
             nickname: String::new(),
             picture: String::new(),
             text: String::new(),
+            twitter: String::new(),
         }
     } else {
         let authors = config
@@ -484,6 +460,8 @@ fn render_single_page(
         authors[0].clone()
     };
 
+    let json_ld = seo::page_json_ld(config, page, &author, image.as_deref());
+
     let globals = liquid::object!({
         "title": page.title,
         "description": page.description,
@@ -495,7 +473,7 @@ fn render_single_page(
         "footer": footer,
         "url": url,
         "image": image,
-        "image_path": image_path,
+        "json_ld": json_ld,
         "site_name": config.site_name,
         "author": author,
     });
@@ -514,9 +492,7 @@ fn test_show_author_text() {
     )
     .unwrap();
     let url = &config.url;
-    let image = false;
-    let image_path = PathBuf::from("");
-    let output = render_single_page(&config, &page, url, image, image_path).unwrap();
+    let output = render_single_page(&config, &page, url, None).unwrap();
     assert!(output.contains("Gabor Szabo"));
     assert!(output.contains("the author of the Rust Maven web site"));
     //assert_eq!(output, "");
@@ -534,13 +510,36 @@ fn test_bad_author() {
     )
     .unwrap();
     let url = &config.url;
-    let image = false;
-    let image_path = PathBuf::from("");
-    let error = render_single_page(&config, &page, url, image, image_path)
-        .err()
-        .unwrap();
+    let error = render_single_page(&config, &page, url, None).err().unwrap();
     assert_eq!(
         error,
         "The nickname 'george' used in the file 'bad_author.md' is not in the config.yaml file."
     );
 }
+
+#[test]
+fn test_get_output_file() {
+    assert_eq!(get_output_file(""), PathBuf::from("index.html"));
+    assert_eq!(get_output_file("about"), PathBuf::from("about.html"));
+    assert_eq!(
+        get_output_file("2024/01/about/"),
+        PathBuf::from("2024/01/about/index.html")
+    );
+}
+
+#[test]
+fn test_author_pictures() {
+    let config = read_config("test_cases/demo").unwrap();
+    assert_eq!(
+        author_pictures(&config, "test_cases/demo").unwrap(),
+        vec![PathBuf::from("images/szabgab.png")]
+    );
+
+    let config = read_config("test_cases/config_with_authors/").unwrap();
+    assert_eq!(
+        author_pictures(&config, "test_cases/config_with_authors/")
+            .err()
+            .unwrap(),
+        "The picture 'images/szabgab.png' of the author 'szabgab' does not exist"
+    );
+}
@@ -1,14 +1,16 @@
 use std::io::Write;
 
 use liquid_core::error::ResultLiquidReplaceExt as _;
-use liquid_core::parser::TryMatchToken;
 use liquid_core::Language;
 use liquid_core::Renderable;
 use liquid_core::Result;
 use liquid_core::Runtime;
-use liquid_core::ValueView as _;
 use liquid_core::{ParseTag, TagReflection, TagTokenIter};
 
+use crate::embed_tag::{parse_start, video};
+use crate::escape;
+use crate::tag_args::parse_named_options;
+
 #[derive(Copy, Clone, Debug, Default)]
 pub struct YoutubeTag;
 
@@ -26,51 +28,24 @@ impl TagReflection for YoutubeTag {
 impl ParseTag for YoutubeTag {
     fn parse(
         &self,
-        mut arguments: TagTokenIter<'_>,
+        arguments: TagTokenIter<'_>,
         _options: &Language,
     ) -> Result<Box<dyn Renderable>> {
-        arguments
-            .expect_next("id expected")?
-            .expect_str("id")
-            .into_result_custom_msg("id expected.")?;
-
-        arguments
-            .expect_next("Assignment operator \"=\" expected.")?
-            .expect_str("=")
-            .into_result_custom_msg("Assignment operator \"=\" expected.")?;
-
-        let id_token = arguments.expect_next("Identifier or value expected")?;
-        let id = match id_token.expect_literal() {
-            TryMatchToken::Matches(name) => name.to_kstr().to_string(),
-            TryMatchToken::Fails(name) => return name.raise_error().into_err(),
-        };
-
-        let key = arguments.next();
-
-        let file = match key {
-            Some(file_token) => {
-                file_token
-                    .expect_str("file")
-                    .into_result_custom_msg("expected file")?;
-                arguments
-                    .expect_next("Assignment operator \"=\" expected.")?
-                    .expect_str("=")
-                    .into_result_custom_msg("Assignment operator \"=\" expected.")?;
-
-                let literal = arguments.expect_next("value of tag")?.expect_literal();
-
-                let file_value = match literal {
-                    TryMatchToken::Matches(name) => name.to_kstr().to_string(),
-                    TryMatchToken::Fails(name) => return name.raise_error().into_err(),
-                };
-                Some(file_value)
-            }
-            None => None,
-        };
-
-        arguments.expect_nothing()?;
-
-        Ok(Box::new(YouTube { id, file }))
+        let mut options =
+            parse_named_options(arguments, "youtube", &["id"], &["file", "start", "title"])?;
+
+        let start = options
+            .remove("start")
+            .map(|start| parse_start(&start))
+            .transpose()
+            .map_err(liquid_core::error::Error::with_msg)?;
+
+        Ok(Box::new(YouTube {
+            id: options.remove("id").unwrap_or_default(),
+            file: options.remove("file"),
+            start,
+            title: options.remove("title"),
+        }))
     }
 
     fn reflection(&self) -> &dyn TagReflection {
@@ -78,20 +53,42 @@ impl ParseTag for YoutubeTag {
     }
 }
 
-#[allow(dead_code)]
 #[derive(Debug)]
 struct YouTube {
     id: String,
+    // A local copy of the video
     file: Option<String>,
+    start: Option<u32>,
+    title: Option<String>,
 }
 
 #[allow(clippy::missing_trait_methods)]
 impl Renderable for YouTube {
-    fn render_to(&self, writer: &mut dyn Write, _runtime: &dyn Runtime) -> Result<()> {
-        let id = self.id.clone();
-        // The optional file field is not used internally, it is not displayed in the output
-        // let file = self.file.clone().unwrap_or_default();
-        writeln!(writer, r#"<iframe width="560" height="315" src="https://www.youtube.com/embed/{id}" title="YouTube video player" frameborder="0" allow="accelerometer; autoplay; clipboard-write; encrypted-media; gyroscope; picture-in-picture; web-share" allowfullscreen></iframe>"#).replace("Failed to render")?;
+    fn render_to(&self, writer: &mut dyn Write, runtime: &dyn Runtime) -> Result<()> {
+        let id = escape(&self.id);
+        let start = self
+            .start
+            .map_or(String::new(), |start| format!("?start={start}"));
+        let title = self.title.as_deref().unwrap_or("YouTube video player");
+
+        // The youtube-nocookie.com domain does not set cookies before the video is played
+        let html = video(
+            runtime,
+            &format!("https://www.youtube-nocookie.com/embed/{id}{start}"),
+            title,
+            Some(&format!("https://i.ytimg.com/vi/{id}/hqdefault.jpg")),
+        );
+
+        let download = self.file.as_ref().map_or(String::new(), |file| {
+            let href = if file.starts_with('/') || file.contains("://") {
+                escape(file)
+            } else {
+                format!("/{}", escape(file))
+            };
+            format!(r#"<p><a href="{href}">Download the video</a></p>"#)
+        });
+
+        writeln!(writer, "{html}{download}").replace("Failed to render")?;
 
         Ok(())
     }
@@ -127,8 +124,27 @@ mod test {
             output,
             format!(
                 "{}\n",
-                r#"<iframe width="560" height="315" src="https://www.youtube.com/embed/R2_D2" title="YouTube video player" frameborder="0" allow="accelerometer; autoplay; clipboard-write; encrypted-media; gyroscope; picture-in-picture; web-share" allowfullscreen></iframe>"#
+                r#"<figure class="image is-16by9"><iframe class="has-ratio" src="https://www.youtube-nocookie.com/embed/R2_D2" title="YouTube video player" frameborder="0" allow="accelerometer; autoplay; clipboard-write; encrypted-media; gyroscope; picture-in-picture; web-share" allowfullscreen></iframe></figure>"#
             )
         );
     }
+
+    #[test]
+    fn youtube_with_file_start_and_title() {
+        let options = options();
+        let template = parser::parse(
+            r#"{% youtube id="R2_D2" file="movie.mp4" start="2:05" title="Droids" %}"#,
+            &options,
+        )
+        .map(runtime::Template::new)
+        .unwrap();
+
+        let runtime = RuntimeBuilder::new().build();
+
+        let output = template.render(&runtime).unwrap();
+        assert!(output.contains(
+            r#"src="https://www.youtube-nocookie.com/embed/R2_D2?start=125" title="Droids""#
+        ));
+        assert!(output.ends_with("</figure><p><a href=\"/movie.mp4\">Download the video</a></p>\n"));
+    }
 }
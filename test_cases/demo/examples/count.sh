@@ -0,0 +1,4 @@
+for n in 1 2 3; do
+    echo $n
+done
+echo Done
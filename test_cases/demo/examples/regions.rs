@@ -0,0 +1,15 @@
+use std::collections::HashMap;
+
+fn main() {
+    // region: setup
+    let mut counter: HashMap<&str, u32> = HashMap::new();
+    // endregion
+
+    for word in ["apple", "banana", "apple"] {
+        // region: count
+        *counter.entry(word).or_insert(0) += 1;
+        // endregion
+    }
+
+    println!("{counter:?}");
+}
@@ -68,7 +68,10 @@ fn test_new_with_parameters() {
         .map(|de| de.unwrap().file_name().to_str().unwrap().to_owned())
         .collect::<Vec<String>>();
     content.sort();
-    assert_eq!(content, [".gitignore", "authors", "config.yaml", "pages"]);
+    assert_eq!(
+        content,
+        [".gitignore", "authors", "config.yaml", "images", "pages"]
+    );
 
     let mut content = tmp_dir
         .path()
@@ -122,6 +125,8 @@ fn test_new_generate() {
     assert!(outdir.join("tags").join("blog.html").exists());
     assert!(outdir.join("tags").join("index.html").exists());
 
+    assert!(outdir.join("images").join("foobar.png").exists());
+
     assert!(outdir.join("img").join("about.png").exists());
     assert!(outdir.join("img").join("archive.png").exists());
     assert!(outdir.join("img").join("index.png").exists());